@@ -0,0 +1,92 @@
+#[cfg(not(feature = "library"))]
+use cosmwasm_std::entry_point;
+use cosmwasm_std::{
+    to_binary, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdResult, SubMsg, WasmMsg,
+};
+use cw2::set_contract_version;
+use cw20_base::state::TOKEN_INFO;
+use cw20_base::ContractError;
+
+use crate::msg::{ExecuteMsg, InstantiateMsg, MinterExecuteMsg, QueryMsg};
+
+const CONTRACT_NAME: &str = "hopers-receipt-token";
+const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn instantiate(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: InstantiateMsg,
+) -> Result<Response, ContractError> {
+    let res = cw20_base::contract::instantiate(deps.branch(), env, info, msg)?;
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+    Ok(res)
+}
+
+// a plain cw20-base token whose transfers also move the staked position,
+// so receipts and stake never drift apart
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn execute(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: ExecuteMsg,
+) -> Result<Response, ContractError> {
+    let minter = TOKEN_INFO.load(deps.storage)?.mint.map(|mint| mint.minter);
+    match &msg {
+        // the minter unbonds the position of the Send sender, which for SendFrom is the spender
+        ExecuteMsg::SendFrom { contract, .. }
+            if minter.as_ref().map(|minter| minter.as_str()) == Some(contract.as_str()) =>
+        {
+            return Err(ContractError::Unauthorized {});
+        }
+        // receipts burned outside an unbond would leave their LP bonded for good
+        ExecuteMsg::Burn { .. } | ExecuteMsg::BurnFrom { .. }
+            if minter.as_ref() != Some(&info.sender) =>
+        {
+            return Err(ContractError::Unauthorized {});
+        }
+        _ => {}
+    }
+
+    let transfer = match &msg {
+        ExecuteMsg::Transfer { recipient, amount } => {
+            Some((info.sender.to_string(), recipient.clone(), *amount))
+        }
+        ExecuteMsg::Send {
+            contract, amount, ..
+        } => Some((info.sender.to_string(), contract.clone(), *amount)),
+        ExecuteMsg::TransferFrom {
+            owner,
+            recipient,
+            amount,
+        } => Some((owner.clone(), recipient.clone(), *amount)),
+        ExecuteMsg::SendFrom {
+            owner,
+            contract,
+            amount,
+            ..
+        } => Some((owner.clone(), contract.clone(), *amount)),
+        _ => None,
+    };
+
+    let mut res = cw20_base::contract::execute(deps.branch(), env, info, msg)?;
+    if let (Some((from, to, amount)), Some(minter)) = (transfer, minter) {
+        // the position moves before a receiving contract acts on the receipts
+        res.messages.insert(
+            0,
+            SubMsg::new(WasmMsg::Execute {
+                contract_addr: minter.to_string(),
+                msg: to_binary(&MinterExecuteMsg::TransferReceipt { from, to, amount })?,
+                funds: vec![],
+            }),
+        );
+    }
+    Ok(res)
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    cw20_base::contract::query(deps, env, msg)
+}
@@ -0,0 +1,17 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::Uint128;
+
+pub use cw20_base::msg::{ExecuteMsg, InstantiateMsg, QueryMsg};
+
+/// Message the staking contract, as minter, receives on every transfer
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum MinterExecuteMsg {
+    TransferReceipt {
+        from: String,
+        to: String,
+        amount: Uint128,
+    },
+}
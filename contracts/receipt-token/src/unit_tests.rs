@@ -0,0 +1,200 @@
+use crate::contract::{execute, instantiate};
+use crate::msg::{ExecuteMsg, InstantiateMsg, MinterExecuteMsg};
+use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+use cosmwasm_std::{to_binary, Binary, DepsMut, SubMsg, Uint128, WasmMsg};
+use cw20::MinterResponse;
+use cw20_base::ContractError;
+
+fn setup_token(deps: DepsMut) {
+    instantiate(
+        deps,
+        mock_env(),
+        mock_info("staking", &[]),
+        InstantiateMsg {
+            name: "Hopers LP Receipt".to_string(),
+            symbol: "sLP".to_string(),
+            decimals: 6,
+            initial_balances: vec![],
+            mint: Some(MinterResponse {
+                minter: "staking".to_string(),
+                cap: None,
+            }),
+            marketing: None,
+        },
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_transfer_notifies_minter() {
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+    setup_token(deps.as_mut());
+
+    // minting and burning are reported by the staking contract itself
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("staking", &[]),
+        ExecuteMsg::Mint {
+            recipient: "user1".to_string(),
+            amount: Uint128::new(1000),
+        },
+    )
+    .unwrap();
+    assert!(res.messages.is_empty());
+
+    let res = execute(
+        deps.as_mut(),
+        env,
+        mock_info("user1", &[]),
+        ExecuteMsg::Transfer {
+            recipient: "user2".to_string(),
+            amount: Uint128::new(400),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(WasmMsg::Execute {
+            contract_addr: "staking".to_string(),
+            msg: to_binary(&MinterExecuteMsg::TransferReceipt {
+                from: "user1".to_string(),
+                to: "user2".to_string(),
+                amount: Uint128::new(400),
+            })
+            .unwrap(),
+            funds: vec![],
+        })]
+    );
+}
+
+#[test]
+fn test_send_from_to_minter() {
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+    setup_token(deps.as_mut());
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("staking", &[]),
+        ExecuteMsg::Mint {
+            recipient: "user1".to_string(),
+            amount: Uint128::new(1000),
+        },
+    )
+    .unwrap();
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("user1", &[]),
+        ExecuteMsg::IncreaseAllowance {
+            spender: "user2".to_string(),
+            amount: Uint128::new(1000),
+            expires: None,
+        },
+    )
+    .unwrap();
+
+    // the minter would unbond the spender's position instead of the owner's
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("user2", &[]),
+        ExecuteMsg::SendFrom {
+            owner: "user1".to_string(),
+            contract: "staking".to_string(),
+            amount: Uint128::new(1000),
+            msg: Binary::default(),
+        },
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized {}));
+
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("user2", &[]),
+        ExecuteMsg::SendFrom {
+            owner: "user1".to_string(),
+            contract: "market".to_string(),
+            amount: Uint128::new(400),
+            msg: Binary::default(),
+        },
+    )
+    .unwrap();
+    execute(
+        deps.as_mut(),
+        env,
+        mock_info("user1", &[]),
+        ExecuteMsg::Send {
+            contract: "staking".to_string(),
+            amount: Uint128::new(600),
+            msg: Binary::default(),
+        },
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_burn_only_by_minter() {
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+    setup_token(deps.as_mut());
+    for recipient in ["user1", "staking"] {
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("staking", &[]),
+            ExecuteMsg::Mint {
+                recipient: recipient.to_string(),
+                amount: Uint128::new(1000),
+            },
+        )
+        .unwrap();
+    }
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("user1", &[]),
+        ExecuteMsg::IncreaseAllowance {
+            spender: "user2".to_string(),
+            amount: Uint128::new(1000),
+            expires: None,
+        },
+    )
+    .unwrap();
+
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("user1", &[]),
+        ExecuteMsg::Burn {
+            amount: Uint128::new(100),
+        },
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized {}));
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("user2", &[]),
+        ExecuteMsg::BurnFrom {
+            owner: "user1".to_string(),
+            amount: Uint128::new(100),
+        },
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized {}));
+
+    // the staking contract burns the receipts sent to unbond
+    execute(
+        deps.as_mut(),
+        env,
+        mock_info("staking", &[]),
+        ExecuteMsg::Burn {
+            amount: Uint128::new(1000),
+        },
+    )
+    .unwrap();
+}
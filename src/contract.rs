@@ -1,25 +1,44 @@
 use cosmwasm_std::{
-    entry_point, from_binary, to_binary, BankMsg, Coin, CosmosMsg, Decimal, Deps, DepsMut, Env,
-    MessageInfo, Response, StdResult, Uint128, WasmMsg,
+    entry_point, from_binary, to_binary, Addr, Api, BankMsg, Coin, CosmosMsg, Decimal256, Deps,
+    DepsMut, Empty, Env, MessageInfo, Order, OverflowError, OverflowOperation, Reply, Response,
+    StdError, StdResult, Storage, SubMsg, Uint128, Uint256, Uint512, WasmMsg,
 };
 
 use crate::error::ContractError;
-use crate::msg::{Cw20HookMsg, ExecuteMsg, InstantiateMsg, MigrateMsg};
-use crate::query::query_all_unbonding_info;
+use crate::msg::{Cw20HookMsg, ExecuteMsg, InstantiateMsg, MigrateMsg, StakeChangedHookMsg};
+use crate::query::{query_all_unbonding_info, query_solvency, query_token_balance};
 use crate::state::{
     staker_info_key, staker_info_storage, unbonding_info_key, unbonding_info_storage,
-    user_earned_info_key, user_earned_info_storage, Config, Denom, StakerInfo, State,
-    UnbondingInfo, UserEarnedInfo, CONFIG, STATE,
+    user_earned_info_key, user_earned_info_storage, AllowlistMode, Config, Denom, EmissionMode,
+    IdleRewardPolicy, LockedNft, StakerInfo, State, UnbondingInfo, UserEarnedInfo, VestingInfo,
+    ALLOWLIST, BONDED_BALANCES, BONDED_TOTAL, CONFIG, HOOKS, LOCKED_NFTS, MEMBERS, REFERRALS,
+    REFERRAL_EARNINGS, STATE, TOTAL, TOTAL_WEIGHT, UNBONDING_BALANCES, UNBONDING_TOTAL, VESTING,
 };
 
 use cw2::{get_contract_version, set_contract_version};
-use cw20::{Cw20ExecuteMsg, Cw20ReceiveMsg};
+use cw20::{Cw20ExecuteMsg, Cw20ReceiveMsg, MinterResponse};
+use cw20_base::msg::InstantiateMsg as Cw20InstantiateMsg;
+use cw4::Cw4Contract;
+use cw721::{Cw721ExecuteMsg, Cw721ReceiveMsg};
+use cw_utils::parse_reply_instantiate_data;
 
 use std::collections::BTreeMap;
+use std::convert::TryFrom;
 
 const CONTRACT_NAME: &str = "Hopers_Staking";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+// The reward index counts rewards per 10^18 bonded units, on top of
+// Decimal256's own 18 decimal places, so that tiny emissions over
+// huge 18-decimal LP bonds do not round down to zero
+pub const REWARD_INDEX_SCALE: u128 = 1_000_000_000_000_000_000;
+
+// Fees are expressed in basis points and capped at 10%
+const FEE_BPS_DENOMINATOR: u64 = 10_000;
+pub const MAX_FEE_BPS: u64 = 1_000;
+
+const RECEIPT_TOKEN_REPLY_ID: u64 = 1;
+
 #[entry_point]
 pub fn instantiate(
     deps: DepsMut,
@@ -30,6 +49,32 @@ pub fn instantiate(
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
 
     deps.api.addr_validate(&msg.lp_token_contract)?;
+    assert_valid_schedule(&msg.distribution_schedule)?;
+    if let Some(treasury) = &msg.treasury {
+        deps.api.addr_validate(treasury)?;
+    }
+    let idle_reward_policy = msg.idle_reward_policy.unwrap_or_default();
+    if idle_reward_policy == IdleRewardPolicy::Treasury && msg.treasury.is_none() {
+        return Err(ContractError::TreasuryNotSet {});
+    }
+    let allowlist_mode = msg.allowlist_mode.unwrap_or_default();
+    assert_valid_allowlist_mode(deps.as_ref(), &allowlist_mode)?;
+    if msg.weight_divisor == Some(Uint128::zero()) {
+        return Err(ContractError::InvalidWeightDivisor {});
+    }
+    let reward_fee_bps = msg.reward_fee_bps.unwrap_or_default();
+    assert_valid_fee(reward_fee_bps, &msg.treasury)?;
+    let deposit_fee_bps = msg.deposit_fee_bps.unwrap_or_default();
+    assert_valid_fee(deposit_fee_bps, &msg.treasury)?;
+    let loyalty_multipliers = msg.loyalty_multipliers.unwrap_or_default();
+    assert_valid_loyalty_multipliers(&loyalty_multipliers)?;
+    let nft_boosts = msg.nft_boosts.unwrap_or_default();
+    assert_valid_nft_boosts(deps.as_ref(), &nft_boosts)?;
+    let referral_fee_bps = msg.referral_fee_bps.unwrap_or_default();
+    assert_valid_referral_fee(referral_fee_bps)?;
+    if let Some(dao) = &msg.dao {
+        deps.api.addr_validate(dao)?;
+    }
 
     CONFIG.save(
         deps.storage,
@@ -39,19 +84,94 @@ pub fn instantiate(
             distribution_schedule: msg.distribution_schedule,
             admin: info.sender.to_string(),
             lock_duration: msg.lock_duration,
+            emission_mode: msg.emission_mode.unwrap_or_default(),
+            treasury: msg.treasury,
+            idle_reward_policy,
+            reward_fee_bps,
+            deposit_fee_bps,
+            min_bond_amount: msg.min_bond_amount.unwrap_or_default(),
+            max_total_bond: msg.max_total_bond,
+            max_bond_per_address: msg.max_bond_per_address,
+            allowlist_mode,
+            weight_divisor: msg.weight_divisor,
+            dao: msg.dao,
+            count_unbonding_power: msg.count_unbonding_power.unwrap_or_default(),
+            receipt_token: None,
+            share_mode: msg.share_mode.unwrap_or_default(),
+            reward_vesting_duration: msg.reward_vesting_duration.unwrap_or_default(),
+            loyalty_multipliers,
+            nft_boosts,
+            referral_fee_bps,
         },
     )?;
+    TOTAL.save(deps.storage, &0)?;
 
     STATE.save(
         deps.storage,
         &State {
             last_distributed: env.block.time.seconds(),
             total_bond_amount: Uint128::zero(),
-            global_reward_index: Decimal::zero(),
+            global_reward_index: Decimal256::zero(),
+            reward_balance: Uint128::zero(),
+            reward_dust: Uint128::zero(),
+            stranded_reward: Uint128::zero(),
+            total_unbonding_amount: Uint128::zero(),
+            total_shares: Uint128::zero(),
+            total_vesting_amount: Uint128::zero(),
+            total_reward_weight: Uint128::zero(),
+            total_referral_reward: Uint128::zero(),
+            total_owed_reward: Uint128::zero(),
         },
     )?;
 
-    Ok(Response::new().add_attribute("action", "instantiate"))
+    // the receipt token address is stored once it replies
+    let mut submsgs = vec![];
+    if let Some(receipt_token) = msg.receipt_token {
+        submsgs.push(SubMsg::reply_on_success(
+            WasmMsg::Instantiate {
+                admin: Some(info.sender.to_string()),
+                code_id: receipt_token.code_id,
+                msg: to_binary(&Cw20InstantiateMsg {
+                    name: receipt_token.name.clone(),
+                    symbol: receipt_token.symbol,
+                    decimals: receipt_token.decimals,
+                    initial_balances: vec![],
+                    mint: Some(MinterResponse {
+                        minter: env.contract.address.to_string(),
+                        cap: None,
+                    }),
+                    marketing: None,
+                })?,
+                funds: vec![],
+                label: receipt_token.name,
+            },
+            RECEIPT_TOKEN_REPLY_ID,
+        ));
+    }
+
+    Ok(Response::new()
+        .add_submessages(submsgs)
+        .add_attribute("action", "instantiate"))
+}
+
+#[entry_point]
+pub fn reply(deps: DepsMut, _env: Env, msg: Reply) -> Result<Response, ContractError> {
+    match msg.id {
+        RECEIPT_TOKEN_REPLY_ID => {
+            let res = parse_reply_instantiate_data(msg)?;
+            let receipt_token = deps.api.addr_validate(&res.contract_address)?;
+
+            let mut config = CONFIG.load(deps.storage)?;
+            config.receipt_token = Some(receipt_token.to_string());
+            CONFIG.save(deps.storage, &config)?;
+
+            Ok(Response::new().add_attributes(vec![
+                ("action", "register_receipt_token"),
+                ("receipt_token", receipt_token.as_str()),
+            ]))
+        }
+        id => Err(ContractError::UnknownReplyId { id }),
+    }
 }
 
 #[entry_point]
@@ -63,9 +183,12 @@ pub fn execute(
 ) -> Result<Response, ContractError> {
     match msg {
         ExecuteMsg::Receive(msg) => receive_cw20(deps, env, info, msg),
+        ExecuteMsg::ReceiveNft(msg) => receive_nft(deps, env, info, msg),
+        ExecuteMsg::UnlockNft { collection } => unlock_nft(deps, env, info, collection),
         ExecuteMsg::Unbond { amount } => unbond(deps, env, info, amount),
         ExecuteMsg::Redeem {} => redeem(deps, env, info),
         ExecuteMsg::Withdraw {} => withdraw(deps, env, info),
+        ExecuteMsg::ClaimVested {} => claim_vested(deps, env, info),
         ExecuteMsg::UpdateConfig {
             distribution_schedule,
         } => update_config(deps, env, info, distribution_schedule),
@@ -80,6 +203,57 @@ pub fn execute(
         ExecuteMsg::UpdateLockDuration { lock_duration } => {
             update_lock_duration(deps, info, lock_duration)
         }
+        ExecuteMsg::UpdateEmissionMode { emission_mode } => {
+            update_emission_mode(deps, env, info, emission_mode)
+        }
+        ExecuteMsg::FundRewards {} => fund_native_rewards(deps, env, info),
+        ExecuteMsg::SweepDust { recipient } => sweep_dust(deps, env, info, recipient),
+        ExecuteMsg::Sweep { denom, recipient } => sweep(deps, env, info, denom, recipient),
+        ExecuteMsg::UpdateTreasury { treasury } => update_treasury(deps, info, treasury),
+        ExecuteMsg::UpdateIdleRewardPolicy { idle_reward_policy } => {
+            update_idle_reward_policy(deps, env, info, idle_reward_policy)
+        }
+        ExecuteMsg::UpdateRewardFee { reward_fee_bps } => {
+            update_reward_fee(deps, info, reward_fee_bps)
+        }
+        ExecuteMsg::UpdateDepositFee { deposit_fee_bps } => {
+            update_deposit_fee(deps, info, deposit_fee_bps)
+        }
+        ExecuteMsg::UpdateMinBondAmount { min_bond_amount } => {
+            update_min_bond_amount(deps, info, min_bond_amount)
+        }
+        ExecuteMsg::UpdateBondCaps {
+            max_total_bond,
+            max_bond_per_address,
+        } => update_bond_caps(deps, info, max_total_bond, max_bond_per_address),
+        ExecuteMsg::UpdateAllowlistMode { allowlist_mode } => {
+            update_allowlist_mode(deps, info, allowlist_mode)
+        }
+        ExecuteMsg::AddToAllowlist { addresses } => update_allowlist(deps, info, addresses, true),
+        ExecuteMsg::RemoveFromAllowlist { addresses } => {
+            update_allowlist(deps, info, addresses, false)
+        }
+        ExecuteMsg::AddHook { addr } => add_hook(deps, info, addr),
+        ExecuteMsg::RemoveHook { addr } => remove_hook(deps, info, addr),
+        ExecuteMsg::UpdateVotingConfig {
+            dao,
+            count_unbonding_power,
+        } => update_voting_config(deps, info, dao, count_unbonding_power),
+        ExecuteMsg::UpdateRewardVestingDuration {
+            reward_vesting_duration,
+        } => update_reward_vesting_duration(deps, info, reward_vesting_duration),
+        ExecuteMsg::UpdateLoyaltyMultipliers {
+            loyalty_multipliers,
+        } => update_loyalty_multipliers(deps, info, loyalty_multipliers),
+        ExecuteMsg::RefreshLoyalty { staker } => refresh_loyalty(deps, env, staker),
+        ExecuteMsg::UpdateNftBoosts { nft_boosts } => update_nft_boosts(deps, info, nft_boosts),
+        ExecuteMsg::UpdateReferralFee { referral_fee_bps } => {
+            update_referral_fee(deps, info, referral_fee_bps)
+        }
+        ExecuteMsg::ClaimReferralRewards {} => claim_referral_rewards(deps, env, info),
+        ExecuteMsg::TransferReceipt { from, to, amount } => {
+            transfer_receipt(deps, env, info, from, to, amount)
+        }
     }
 }
 
@@ -93,14 +267,39 @@ pub fn receive_cw20(
     let token_contract = info.sender.to_string();
 
     match from_binary(&cw20_msg.msg) {
-        Ok(Cw20HookMsg::Bond {}) => {
+        Ok(Cw20HookMsg::Bond { referrer }) => {
             // only staking token contract can execute this message
             if config.lp_token_contract != token_contract {
                 return Err(ContractError::WrongContractError {});
             }
 
             let cw20_sender = cw20_msg.sender;
-            bond(deps, env, cw20_sender, cw20_msg.amount)
+            bond(deps, env, cw20_sender, cw20_msg.amount, referrer)
+        }
+        Ok(Cw20HookMsg::FundRewards {}) => {
+            // only reward token contract can execute this message
+            if config.reward_token != Denom::Cw20(info.sender) {
+                return Err(ContractError::WrongContractError {});
+            }
+
+            fund_rewards(deps, env, cw20_msg.amount)
+        }
+        Ok(Cw20HookMsg::Compound {}) => {
+            // only staking token contract can execute this message
+            if config.lp_token_contract != token_contract {
+                return Err(ContractError::WrongContractError {});
+            }
+
+            compound(deps, cw20_msg.sender, cw20_msg.amount)
+        }
+        Ok(Cw20HookMsg::Unbond {}) => {
+            // only the receipt token contract can execute this message
+            if config.receipt_token.as_deref() != Some(token_contract.as_str()) {
+                return Err(ContractError::WrongContractError {});
+            }
+
+            let staker = deps.api.addr_validate(&cw20_msg.sender)?;
+            unbond_receipts(deps, env, staker, token_contract, cw20_msg.amount)
         }
         Err(_) => return Err(ContractError::DataShouldBeGiven {}),
     }
@@ -111,51 +310,155 @@ pub fn bond(
     env: Env,
     sender_addr: String,
     amount: Uint128,
+    referrer: Option<String>,
 ) -> Result<Response, ContractError> {
     let config = CONFIG.load(deps.storage)?;
     let mut state = STATE.load(deps.storage)?;
 
+    assert_allowlisted(deps.as_ref(), &config, &sender_addr)?;
+
     let staker_info_key = staker_info_key(&sender_addr);
     let mut staker_info: StakerInfo;
     match staker_info_storage().may_load(deps.storage, staker_info_key.clone())? {
         Some(some_staker_info) => staker_info = some_staker_info,
-        None => {
-            staker_info = StakerInfo {
-                reward_index: Decimal::zero(),
-                bond_amount: Uint128::zero(),
-                pending_reward: Uint128::zero(),
-                address: sender_addr.clone(),
-            }
-        }
+        None => staker_info = StakerInfo::new(sender_addr.clone()),
     };
 
-    // Compute global reward & staker reward
-    compute_reward(&config, &mut state, env.block.time.seconds());
-    compute_staker_reward(&state, &mut staker_info)?;
+    // the referrer is recorded by the bond opening the position, which may only hold NFTs so far
+    if staker_info.shares.is_zero() && staker_info.referrer.is_none() {
+        staker_info.referrer = referrer
+            .map(|referrer| record_referral(deps.storage, deps.api, &sender_addr, &referrer))
+            .transpose()?;
+    }
+
+    // Compute global reward & staker reward, then increase bond_amount
+    // by the amount left after the deposit fee
+    let shares = staker_info.shares;
+    let fee = apply_bond(
+        &config,
+        &mut state,
+        &mut staker_info,
+        amount,
+        env.block.time.seconds(),
+    )?;
+    let amount = amount.checked_sub(fee)?;
+    let shares = staker_info.shares.checked_sub(shares)?;
+
+    // Send rewards emitted while nothing was bonded to the treasury
+    let mut msgs = take_stranded_reward(&config, &mut state)?;
+    if !fee.is_zero() {
+        let treasury = config
+            .treasury
+            .clone()
+            .ok_or(ContractError::TreasuryNotSet {})?;
+        msgs.push(token_transfer_msg(
+            &Denom::Cw20(Addr::unchecked(&config.lp_token_contract)),
+            treasury,
+            fee,
+        )?);
+    }
 
-    // Increase bond_amount
-    increase_bond_amount(&mut state, &mut staker_info, amount);
+    if let Some(receipt_token) = &config.receipt_token {
+        msgs.push(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: receipt_token.clone(),
+            msg: to_binary(&Cw20ExecuteMsg::Mint {
+                recipient: sender_addr.clone(),
+                amount: shares,
+            })?,
+            funds: vec![],
+        }));
+    }
 
     // Store updated state with staker's staker_info
+    credit_referral_reward(deps.storage, &mut state, &mut staker_info)?;
     staker_info_storage().save(deps.storage, staker_info_key.clone(), &staker_info)?;
+    snapshot_bond(
+        deps.storage,
+        &config,
+        env.block.height,
+        &state,
+        &staker_info,
+    )?;
     STATE.save(deps.storage, &state)?;
 
-    Ok(Response::new().add_attributes(vec![
-        ("action", "bond"),
-        ("owner", sender_addr.as_str()),
-        ("amount", amount.to_string().as_str()),
-    ]))
+    let hook_msgs = stake_changed_hook_msgs(
+        deps.storage,
+        StakeChangedHookMsg::Stake {
+            addr: sender_addr.clone(),
+            amount,
+        },
+    )?;
+
+    Ok(Response::new()
+        .add_messages(msgs)
+        .add_submessages(hook_msgs)
+        .add_attributes(vec![
+            ("action", "bond"),
+            ("owner", sender_addr.as_str()),
+            ("amount", amount.to_string().as_str()),
+            ("shares", shares.to_string().as_str()),
+            ("fee", fee.to_string().as_str()),
+        ]))
 }
 
+// receipt holders unbond by sending their receipts instead
 pub fn unbond(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
     amount: Uint128,
 ) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    if config.receipt_token.is_some() {
+        return Err(ContractError::ReceiptsRequired {});
+    }
+
+    let (res, _) = unbond_position(deps, env, info.sender, amount)?;
+    Ok(res)
+}
+
+// the receipts are shares; unbonding the LP they back rounds the shares
+// burned up, so any receipt not burned goes back to the staker
+fn unbond_receipts(
+    deps: DepsMut,
+    env: Env,
+    staker: Addr,
+    receipt_token: String,
+    receipts: Uint128,
+) -> Result<Response, ContractError> {
+    let state = STATE.load(deps.storage)?;
+    let amount = shares_to_amount(&state, receipts)?;
+    let (res, shares) = unbond_position(deps, env, staker.clone(), amount)?;
+
+    let mut msgs = vec![CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: receipt_token.clone(),
+        msg: to_binary(&Cw20ExecuteMsg::Burn { amount: shares })?,
+        funds: vec![],
+    })];
+    let unburned = receipts.checked_sub(shares)?;
+    if !unburned.is_zero() {
+        msgs.push(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: receipt_token,
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: staker.to_string(),
+                amount: unburned,
+            })?,
+            funds: vec![],
+        }));
+    }
+    Ok(res.add_messages(msgs))
+}
+
+// returns the shares burned
+fn unbond_position(
+    deps: DepsMut,
+    env: Env,
+    staker: Addr,
+    amount: Uint128,
+) -> Result<(Response, Uint128), ContractError> {
     let config = CONFIG.load(deps.storage)?;
     let mut state = STATE.load(deps.storage)?;
-    let sender_addr = info.sender.to_string();
+    let sender_addr = staker.to_string();
     let time = env.block.time.seconds();
 
     let staker_info_key = staker_info_key(&sender_addr);
@@ -165,21 +468,40 @@ pub fn unbond(
         None => return Err(ContractError::NotStaked {}),
     };
 
-    if staker_info.bond_amount < amount {
-        return Err(ContractError::ExceedBondAmount {});
-    }
+    // Compute global reward & staker reward, then decrease bond_amount
+    let shares = staker_info.shares;
+    apply_unbond(&config, &mut state, &mut staker_info, amount, time)?;
+    let shares = shares.checked_sub(staker_info.shares)?;
+    state.total_unbonding_amount = state.total_unbonding_amount.checked_add(amount)?;
 
-    // Compute global reward & staker reward
-    compute_reward(&config, &mut state, env.block.time.seconds());
-    compute_staker_reward(&state, &mut staker_info)?;
-
-    // Decrease bond_amount
-    decrease_bond_amount(&mut state, &mut staker_info, amount)?;
+    snapshot_bond(
+        deps.storage,
+        &config,
+        env.block.height,
+        &state,
+        &staker_info,
+    )?;
+    // unbonding power is snapshotted in the shares burned, like bonded power
+    UNBONDING_BALANCES.update(
+        deps.storage,
+        &staker,
+        env.block.height,
+        |balance| -> StdResult<_> { Ok(balance.unwrap_or_default().checked_add(shares)?) },
+    )?;
+    let unbonding_total = UNBONDING_TOTAL
+        .may_load(deps.storage)?
+        .unwrap_or_default()
+        .checked_add(shares)?;
+    UNBONDING_TOTAL.save(deps.storage, &unbonding_total, env.block.height)?;
 
     // Store or remove updated rewards info
     // depends on the left pending reward and bond amount
-    if staker_info.pending_reward.is_zero() && staker_info.bond_amount.is_zero() {
-        staker_info_storage().remove(deps.storage, staker_info_key)?;
+    credit_referral_reward(deps.storage, &mut state, &mut staker_info)?;
+    if staker_info.pending_reward.is_zero()
+        && staker_info.shares.is_zero()
+        && staker_info.nft_boost.is_zero()
+    {
+        remove_staker_info(deps.storage, staker_info_key, &staker_info)?;
     } else {
         staker_info_storage().save(deps.storage, staker_info_key, &staker_info)?;
     }
@@ -187,38 +509,380 @@ pub fn unbond(
     // Store updated state
     STATE.save(deps.storage, &state)?;
 
+    // unbonds within the same block share one entry
     let unbonding_info_key = unbonding_info_key(&sender_addr, time);
+    let unbonding_amount =
+        match unbonding_info_storage().may_load(deps.storage, unbonding_info_key.clone())? {
+            Some(unbonding_info) => unbonding_info.amount.checked_add(amount)?,
+            None => amount,
+        };
     unbonding_info_storage().save(
         deps.storage,
         unbonding_info_key,
         &UnbondingInfo {
             address: sender_addr.clone(),
-            amount,
+            amount: unbonding_amount,
             time,
         },
     )?;
 
+    let hook_msgs = stake_changed_hook_msgs(
+        deps.storage,
+        StakeChangedHookMsg::Unstake {
+            addr: sender_addr.clone(),
+            amount,
+        },
+    )?;
+
+    let res = Response::new()
+        .add_submessages(hook_msgs)
+        .add_attributes(vec![
+            ("action", "unbond"),
+            ("owner", staker.as_str()),
+            ("amount", amount.to_string().as_str()),
+            ("shares", shares.to_string().as_str()),
+        ]);
+    Ok((res, shares))
+}
+
+// LP added without minting shares raises the value of every share
+pub fn compound(
+    deps: DepsMut,
+    sender_addr: String,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    if sender_addr != config.admin {
+        return Err(ContractError::Unauthorized {});
+    }
+    if !config.share_mode {
+        return Err(ContractError::ShareModeDisabled {});
+    }
+
+    let mut state = STATE.load(deps.storage)?;
+    if state.total_shares.is_zero() {
+        return Err(ContractError::NothingToCompound {});
+    }
+    state.total_bond_amount = state.total_bond_amount.checked_add(amount)?;
+    STATE.save(deps.storage, &state)?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "compound"),
+        ("amount", amount.to_string().as_str()),
+    ]))
+}
+
+// rewards follow the receipt token, so a transfer settles both positions
+// and moves the shares, with their share of the bonded principal
+pub fn transfer_receipt(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    from: String,
+    to: String,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    if config.receipt_token.as_deref() != Some(info.sender.as_str()) {
+        return Err(ContractError::Unauthorized {});
+    }
+    let from = deps.api.addr_validate(&from)?;
+    let to = deps.api.addr_validate(&to)?;
+    // both sides would load the same position and the second save would win;
+    // receipts sent to or returned by this contract are unbonding, not moving
+    if from == to || from == env.contract.address || to == env.contract.address {
+        return Ok(Response::new().add_attributes(vec![("action", "transfer_receipt")]));
+    }
+    let time = env.block.time.seconds();
+    let mut state = STATE.load(deps.storage)?;
+
+    let from_key = staker_info_key(&from.to_string());
+    let mut from_info = staker_info_storage()
+        .may_load(deps.storage, from_key.clone())?
+        .ok_or(ContractError::NotStaked {})?;
+    if from_info.shares < amount {
+        return Err(ContractError::ExceedBondAmount {});
+    }
+    let to_key = staker_info_key(&to.to_string());
+    let mut to_info = staker_info_storage()
+        .may_load(deps.storage, to_key.clone())?
+        .unwrap_or_else(|| StakerInfo::new(to.to_string()));
+
+    compute_reward(&config, &mut state, time)?;
+    compute_staker_reward(&config, &state, &mut from_info)?;
+    compute_staker_reward(&config, &state, &mut to_info)?;
+    let principal = from_info
+        .bond_amount
+        .multiply_ratio(amount, from_info.shares);
+    // the receiver bonds as if it staked the principal itself,
+    // the total is unchanged so the total cap cannot be crossed
+    assert_allowlisted(deps.as_ref(), &config, to.as_str())?;
+    assert_position_limits(&config, &to_info, principal)?;
+    let (from_shares, to_shares) = (from_info.shares, to_info.shares);
+    from_info.shares = from_info.shares.checked_sub(amount)?;
+    from_info.bond_amount = from_info.bond_amount.checked_sub(principal)?;
+    to_info.shares = to_info.shares.checked_add(amount)?;
+    to_info.bond_amount = to_info.bond_amount.checked_add(principal)?;
+    update_loyalty(&config, &mut state, &mut from_info, from_shares, time)?;
+    update_loyalty(&config, &mut state, &mut to_info, to_shares, time)?;
+
+    credit_referral_reward(deps.storage, &mut state, &mut from_info)?;
+    credit_referral_reward(deps.storage, &mut state, &mut to_info)?;
+    if from_info.pending_reward.is_zero()
+        && from_info.shares.is_zero()
+        && from_info.nft_boost.is_zero()
+    {
+        remove_staker_info(deps.storage, from_key, &from_info)?;
+    } else {
+        staker_info_storage().save(deps.storage, from_key, &from_info)?;
+    }
+    staker_info_storage().save(deps.storage, to_key, &to_info)?;
+    snapshot_bond(deps.storage, &config, env.block.height, &state, &from_info)?;
+    snapshot_bond(deps.storage, &config, env.block.height, &state, &to_info)?;
+    STATE.save(deps.storage, &state)?;
+
+    let mut hook_msgs = stake_changed_hook_msgs(
+        deps.storage,
+        StakeChangedHookMsg::Unstake {
+            addr: from.to_string(),
+            amount: principal,
+        },
+    )?;
+    hook_msgs.extend(stake_changed_hook_msgs(
+        deps.storage,
+        StakeChangedHookMsg::Stake {
+            addr: to.to_string(),
+            amount: principal,
+        },
+    )?);
+
+    Ok(Response::new()
+        .add_submessages(hook_msgs)
+        .add_attributes(vec![
+            ("action", "transfer_receipt"),
+            ("from", from.as_str()),
+            ("to", to.as_str()),
+            ("amount", amount.to_string().as_str()),
+        ]))
+}
+
+// the sending collection must be boosted, the NFT boosts its previous owner
+pub fn receive_nft(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: Cw721ReceiveMsg,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let collection = info.sender;
+    let boost = config
+        .nft_boosts
+        .iter()
+        .find(|nft_boost| nft_boost.0 == collection.as_str())
+        .map(|nft_boost| nft_boost.1)
+        .ok_or(ContractError::NftCollectionNotAllowed {})?;
+
+    let staker = deps.api.addr_validate(&msg.sender)?;
+    if LOCKED_NFTS.has(deps.storage, (&staker, &collection)) {
+        return Err(ContractError::NftAlreadyLocked {});
+    }
+
+    let mut state = STATE.load(deps.storage)?;
+    let staker_info_key = staker_info_key(&msg.sender);
+    let mut staker_info = staker_info_storage()
+        .may_load(deps.storage, staker_info_key.clone())?
+        .unwrap_or_else(|| StakerInfo::new(msg.sender.clone()));
+
+    // Compute global reward & staker reward, then boost the reward weight
+    let time = env.block.time.seconds();
+    compute_reward(&config, &mut state, time)?;
+    compute_staker_reward(&config, &state, &mut staker_info)?;
+    staker_info.nft_boost = Decimal256::new(
+        staker_info
+            .nft_boost
+            .atomics()
+            .checked_add(boost.atomics())?,
+    );
+    update_reward_weight(&config, &mut state, &mut staker_info, time)?;
+
+    LOCKED_NFTS.save(
+        deps.storage,
+        (&staker, &collection),
+        &LockedNft {
+            token_id: msg.token_id.clone(),
+            boost,
+        },
+    )?;
+    credit_referral_reward(deps.storage, &mut state, &mut staker_info)?;
+    staker_info_storage().save(deps.storage, staker_info_key, &staker_info)?;
+    STATE.save(deps.storage, &state)?;
+
     Ok(Response::new().add_attributes(vec![
-        ("action", "unbond"),
+        ("action", "lock_nft"),
+        ("owner", staker.as_str()),
+        ("collection", collection.as_str()),
+        ("token_id", msg.token_id.as_str()),
+    ]))
+}
+
+pub fn unlock_nft(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    collection: String,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let collection = deps.api.addr_validate(&collection)?;
+    let locked_nft = LOCKED_NFTS
+        .may_load(deps.storage, (&info.sender, &collection))?
+        .ok_or(ContractError::NftNotLocked {})?;
+
+    let mut state = STATE.load(deps.storage)?;
+    let staker_info_key = staker_info_key(&info.sender.to_string());
+    let mut staker_info = staker_info_storage()
+        .may_load(deps.storage, staker_info_key.clone())?
+        .ok_or(ContractError::NotStaked {})?;
+
+    // Compute global reward & staker reward, then take the boost back
+    let time = env.block.time.seconds();
+    compute_reward(&config, &mut state, time)?;
+    compute_staker_reward(&config, &state, &mut staker_info)?;
+    staker_info.nft_boost = Decimal256::new(
+        staker_info
+            .nft_boost
+            .atomics()
+            .checked_sub(locked_nft.boost.atomics())?,
+    );
+    update_reward_weight(&config, &mut state, &mut staker_info, time)?;
+
+    LOCKED_NFTS.remove(deps.storage, (&info.sender, &collection));
+    credit_referral_reward(deps.storage, &mut state, &mut staker_info)?;
+    if staker_info.pending_reward.is_zero()
+        && staker_info.shares.is_zero()
+        && staker_info.nft_boost.is_zero()
+    {
+        remove_staker_info(deps.storage, staker_info_key, &staker_info)?;
+    } else {
+        staker_info_storage().save(deps.storage, staker_info_key, &staker_info)?;
+    }
+    STATE.save(deps.storage, &state)?;
+
+    Ok(Response::new()
+        .add_message(WasmMsg::Execute {
+            contract_addr: collection.to_string(),
+            msg: to_binary(&Cw721ExecuteMsg::TransferNft {
+                recipient: info.sender.to_string(),
+                token_id: locked_nft.token_id.clone(),
+            })?,
+            funds: vec![],
+        })
+        .add_attributes(vec![
+            ("action", "unlock_nft"),
+            ("owner", info.sender.as_str()),
+            ("collection", collection.as_str()),
+            ("token_id", locked_nft.token_id.as_str()),
+        ]))
+}
+
+// referral rewards are charged the reward fee like withdrawals
+pub fn claim_referral_rewards(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let mut state = STATE.load(deps.storage)?;
+    let mut earnings = REFERRAL_EARNINGS
+        .may_load(deps.storage, &info.sender)?
+        .unwrap_or_default();
+    if earnings.pending.is_zero() {
+        return Err(ContractError::NothingToClaim {});
+    }
+
+    let amount = earnings.pending;
+    earnings.pending = Uint128::zero();
+    REFERRAL_EARNINGS.save(deps.storage, &info.sender, &earnings)?;
+    state.total_referral_reward = state.total_referral_reward.checked_sub(amount)?;
+    STATE.save(deps.storage, &state)?;
+
+    let fee = reward_fee(&config, amount);
+    let amount = amount.checked_sub(fee)?;
+    let mut msgs = vec![token_transfer_msg(
+        &config.reward_token,
+        info.sender.to_string(),
+        amount,
+    )?];
+    if !fee.is_zero() {
+        let treasury = config
+            .treasury
+            .clone()
+            .ok_or(ContractError::TreasuryNotSet {})?;
+        msgs.push(token_transfer_msg(&config.reward_token, treasury, fee)?);
+    }
+
+    Ok(Response::new().add_messages(msgs).add_attributes(vec![
+        ("action", "claim_referral_rewards"),
         ("owner", info.sender.as_str()),
         ("amount", amount.to_string().as_str()),
+        ("fee", fee.to_string().as_str()),
     ]))
 }
 
+pub fn claim_vested(deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let mut vesting = VESTING
+        .may_load(deps.storage, &info.sender)?
+        .ok_or(ContractError::NothingToClaim {})?;
+    settle_vesting(&mut vesting, env.block.time.seconds())?;
+
+    let amount = vesting.vested_amount;
+    if amount.is_zero() {
+        return Err(ContractError::NothingToClaim {});
+    }
+    vesting.vested_amount = Uint128::zero();
+    if vesting.locked_amount.is_zero() {
+        VESTING.remove(deps.storage, &info.sender);
+    } else {
+        VESTING.save(deps.storage, &info.sender, &vesting)?;
+    }
+
+    let mut state = STATE.load(deps.storage)?;
+    state.total_vesting_amount = state.total_vesting_amount.checked_sub(amount)?;
+    STATE.save(deps.storage, &state)?;
+
+    Ok(Response::new()
+        .add_message(token_transfer_msg(
+            &config.reward_token,
+            info.sender.to_string(),
+            amount,
+        )?)
+        .add_attributes(vec![
+            ("action", "claim_vested"),
+            ("owner", info.sender.as_str()),
+            ("amount", amount.to_string().as_str()),
+        ]))
+}
+
 pub fn redeem(deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response, ContractError> {
     let sender_addr = info.sender.to_string();
     let crr_time = env.block.time.seconds();
+    let height = env.block.height;
 
     let config = CONFIG.load(deps.storage)?;
 
     let mut amount = Uint128::zero();
 
     let unbonding_infos = query_all_unbonding_info(deps.as_ref(), env, sender_addr.clone())?;
+    let unbonding_amount = unbonding_infos
+        .iter()
+        .try_fold(Uint128::zero(), |total, unbonding_info| {
+            total.checked_add(unbonding_info.amount)
+        })?;
     for unbonding_info in unbonding_infos {
-        if unbonding_info.time + config.lock_duration > crr_time {
+        if unbonding_info.time.saturating_add(config.lock_duration) > crr_time {
             break;
         } else {
-            amount += unbonding_info.amount;
+            amount = amount.checked_add(unbonding_info.amount)?;
             let unbonding_info_key = unbonding_info_key(&sender_addr, unbonding_info.time);
             unbonding_info_storage().remove(deps.storage, unbonding_info_key.clone())?;
         }
@@ -228,6 +892,26 @@ pub fn redeem(deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response, Co
         return Err(ContractError::NothingToRedeem {});
     }
 
+    let mut state = STATE.load(deps.storage)?;
+    state.total_unbonding_amount = state.total_unbonding_amount.checked_sub(amount)?;
+    STATE.save(deps.storage, &state)?;
+    // the unbonding power held in shares leaves in proportion to the LP redeemed
+    let balance = UNBONDING_BALANCES
+        .may_load(deps.storage, &info.sender)?
+        .unwrap_or_default();
+    let shares = balance.multiply_ratio(amount, unbonding_amount);
+    UNBONDING_BALANCES.save(
+        deps.storage,
+        &info.sender,
+        &balance.checked_sub(shares)?,
+        height,
+    )?;
+    let unbonding_total = UNBONDING_TOTAL
+        .may_load(deps.storage)?
+        .unwrap_or_default()
+        .checked_sub(shares)?;
+    UNBONDING_TOTAL.save(deps.storage, &unbonding_total, height)?;
+
     Ok(Response::new()
         .add_messages(vec![CosmosMsg::Wasm(WasmMsg::Execute {
             contract_addr: config.lp_token_contract,
@@ -258,14 +942,21 @@ pub fn withdraw(deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response,
         None => return Err(ContractError::NotStaked {}),
     };
 
-    // Compute global reward & staker reward
-    compute_reward(&config, &mut state, env.block.time.seconds());
-    compute_staker_reward(&state, &mut staker_info)?;
+    // Compute global reward & staker reward, then take the pending reward
+    let amount = apply_withdraw(
+        &config,
+        &mut state,
+        &mut staker_info,
+        env.block.time.seconds(),
+    )?;
+
+    let fee = reward_fee(&config, amount);
+    let amount = amount.checked_sub(fee)?;
 
     let user_earned_info_key = user_earned_info_key(&sender_addr);
     match user_earned_info_storage().may_load(deps.storage, user_earned_info_key.clone())? {
         Some(mut user_earned_info) => {
-            user_earned_info.total_earned += staker_info.pending_reward;
+            user_earned_info.total_earned = user_earned_info.total_earned.checked_add(amount)?;
             user_earned_info_storage().save(
                 deps.storage,
                 user_earned_info_key,
@@ -278,74 +969,144 @@ pub fn withdraw(deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response,
                 user_earned_info_key,
                 &UserEarnedInfo {
                     address: sender_addr,
-                    total_earned: staker_info.pending_reward,
+                    total_earned: amount,
                 },
             )?;
         }
     }
 
-    let amount = staker_info.pending_reward;
-    staker_info.pending_reward = Uint128::zero();
-
     // Store or remove updated rewards info
     // depends on the left pending reward and bond amount
-    if staker_info.bond_amount.is_zero() {
-        staker_info_storage().remove(deps.storage, staker_info_key)?;
+    credit_referral_reward(deps.storage, &mut state, &mut staker_info)?;
+    if staker_info.shares.is_zero() && staker_info.nft_boost.is_zero() {
+        remove_staker_info(deps.storage, staker_info_key, &staker_info)?;
     } else {
         staker_info_storage().save(deps.storage, staker_info_key, &staker_info)?;
     }
 
+    // rewards withdrawn under vesting are only paid out by ClaimVested
+    let mut msgs = vec![];
+    if config.reward_vesting_duration == 0 {
+        msgs.push(token_transfer_msg(
+            &config.reward_token,
+            info.sender.to_string(),
+            amount,
+        )?);
+    } else if !amount.is_zero() {
+        let time = env.block.time.seconds();
+        let mut vesting = VESTING
+            .may_load(deps.storage, &info.sender)?
+            .unwrap_or_default();
+        settle_vesting(&mut vesting, time)?;
+        vesting.locked_amount = vesting.locked_amount.checked_add(amount)?;
+        vesting.start_time = time;
+        vesting.end_time = time.saturating_add(config.reward_vesting_duration);
+        VESTING.save(deps.storage, &info.sender, &vesting)?;
+        state.total_vesting_amount = state.total_vesting_amount.checked_add(amount)?;
+    }
+
     // Store updated state
     STATE.save(deps.storage, &state)?;
 
-    let reward_msg: CosmosMsg;
-
-    match config.reward_token {
-        Denom::Native(denom) => {
-            reward_msg = CosmosMsg::Bank(BankMsg::Send {
-                to_address: info.sender.to_string(),
-                amount: vec![Coin { denom, amount }],
-            })
-        }
-        Denom::Cw20(address) => {
-            reward_msg = CosmosMsg::Wasm(WasmMsg::Execute {
-                contract_addr: address.to_string(),
-                msg: to_binary(&Cw20ExecuteMsg::Transfer {
-                    recipient: info.sender.to_string(),
-                    amount,
-                })?,
-                funds: vec![],
-            });
-        }
+    if !fee.is_zero() {
+        let treasury = config
+            .treasury
+            .clone()
+            .ok_or(ContractError::TreasuryNotSet {})?;
+        msgs.push(token_transfer_msg(&config.reward_token, treasury, fee)?);
     }
 
-    Ok(Response::new().add_message(reward_msg).add_attributes(vec![
+    Ok(Response::new().add_messages(msgs).add_attributes(vec![
         ("action", "withdraw"),
         ("owner", info.sender.as_str()),
         ("amount", amount.to_string().as_str()),
+        ("fee", fee.to_string().as_str()),
     ]))
 }
 
-pub fn migrate_staking(
+// build the message sending `amount` of `denom` to `recipient`
+fn token_transfer_msg(denom: &Denom, recipient: String, amount: Uint128) -> StdResult<CosmosMsg> {
+    let msg = match denom {
+        Denom::Native(denom) => CosmosMsg::Bank(BankMsg::Send {
+            to_address: recipient,
+            amount: vec![Coin {
+                denom: denom.clone(),
+                amount,
+            }],
+        }),
+        Denom::Cw20(address) => CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: address.to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Transfer { recipient, amount })?,
+            funds: vec![],
+        }),
+    };
+    Ok(msg)
+}
+
+pub fn fund_native_rewards(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
-    new_staking_contract: String,
 ) -> Result<Response, ContractError> {
-    let sender_addr = info.sender.to_string();
+    let config = CONFIG.load(deps.storage)?;
 
-    let mut config = CONFIG.load(deps.storage)?;
-    let mut state = STATE.load(deps.storage)?;
+    let denom = match config.reward_token {
+        Denom::Native(denom) => denom,
+        Denom::Cw20(_) => return Err(ContractError::WrongContractError {}),
+    };
 
-    if sender_addr != config.admin {
+    let amount = info
+        .funds
+        .iter()
+        .filter(|coin| coin.denom == denom)
+        .try_fold(Uint128::zero(), |acc, coin| acc.checked_add(coin.amount))?;
+
+    fund_rewards(deps, env, amount)
+}
+
+pub fn fund_rewards(deps: DepsMut, env: Env, amount: Uint128) -> Result<Response, ContractError> {
+    if amount.is_zero() {
+        return Err(ContractError::NoRewardFunds {});
+    }
+
+    let config = CONFIG.load(deps.storage)?;
+    let mut state = STATE.load(deps.storage)?;
+
+    // settle emission so far, so the new funds only start emitting from now
+    compute_reward(&config, &mut state, env.block.time.seconds())?;
+    state.reward_balance = state.reward_balance.checked_add(amount)?;
+
+    STATE.save(deps.storage, &state)?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "fund_rewards"),
+        ("amount", amount.to_string().as_str()),
+        ("reward_balance", state.reward_balance.to_string().as_str()),
+    ]))
+}
+
+pub fn migrate_staking(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    new_staking_contract: String,
+) -> Result<Response, ContractError> {
+    let sender_addr = info.sender.to_string();
+
+    let mut config = CONFIG.load(deps.storage)?;
+    let mut state = STATE.load(deps.storage)?;
+
+    if sender_addr != config.admin {
         return Err(ContractError::Unauthorized {});
     }
 
     // compute global reward, sets last_distributed_seconds to env.block.time.seconds
-    compute_reward(&config, &mut state, env.block.time.seconds());
+    compute_reward(&config, &mut state, env.block.time.seconds())?;
 
-    let total_distribution_amount: Uint128 =
-        config.distribution_schedule.iter().map(|item| item.2).sum();
+    let total_distribution_amount = config
+        .distribution_schedule
+        .iter()
+        .try_fold(Uint128::zero(), |acc, item| acc.checked_add(item.2))?;
 
     let block_time = env.block.time.seconds();
     // eliminate distribution slots that have not started
@@ -357,16 +1118,13 @@ pub fn migrate_staking(
     for s in config.distribution_schedule.iter_mut() {
         if s.1 < block_time {
             // all distributed
-            distributed_amount += s.2;
+            distributed_amount = distributed_amount.checked_add(s.2)?;
         } else {
             // partially distributed slot
-            let whole_time = s.1 - s.0;
-            let distribution_amount_per_second: Decimal = Decimal::from_ratio(s.2, whole_time);
-
-            let passed_time = block_time - s.0;
-            let distributed_amount_on_slot =
-                distribution_amount_per_second * Uint128::from(passed_time as u128);
-            distributed_amount += distributed_amount_on_slot;
+            let whole_time = slot_duration(s)?;
+            let passed_time = checked_time_sub(block_time, s.0)?;
+            let distributed_amount_on_slot = s.2.multiply_ratio(passed_time, whole_time);
+            distributed_amount = distributed_amount.checked_add(distributed_amount_on_slot)?;
 
             // modify distribution slot
             s.1 = block_time;
@@ -410,14 +1168,12 @@ pub fn update_config(
 
     authcheck(deps.as_ref(), &info)?;
 
+    assert_valid_schedule(&distribution_schedule)?;
     assert_new_schedules(&config, &state, distribution_schedule.clone())?;
 
     let new_config = Config {
-        admin: config.admin,
-        lp_token_contract: config.lp_token_contract,
-        reward_token: config.reward_token,
         distribution_schedule,
-        lock_duration: config.lock_duration,
+        ..config
     };
     CONFIG.save(deps.storage, &new_config)?;
 
@@ -474,118 +1230,1269 @@ pub fn update_lock_duration(
     Ok(Response::new().add_attributes(vec![("action", "update_lock_duration")]))
 }
 
-fn authcheck(deps: Deps, info: &MessageInfo) -> Result<(), ContractError> {
+pub fn update_emission_mode(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    emission_mode: EmissionMode,
+) -> Result<Response, ContractError> {
+    let mut config = CONFIG.load(deps.storage)?;
+    let mut state = STATE.load(deps.storage)?;
+
+    authcheck(deps.as_ref(), &info)?;
+
+    // settle everything emitted under the old mode before switching
+    compute_reward(&config, &mut state, env.block.time.seconds())?;
+    config.emission_mode = emission_mode;
+
+    CONFIG.save(deps.storage, &config)?;
+    STATE.save(deps.storage, &state)?;
+
+    Ok(Response::new().add_attributes(vec![("action", "update_emission_mode")]))
+}
+
+pub fn sweep_dust(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    recipient: String,
+) -> Result<Response, ContractError> {
     let config = CONFIG.load(deps.storage)?;
-    if info.sender != config.admin {
-        return Err(ContractError::Unauthorized {});
+    let mut state = STATE.load(deps.storage)?;
+
+    authcheck(deps.as_ref(), &info)?;
+    deps.api.addr_validate(&recipient)?;
+
+    compute_reward(&config, &mut state, env.block.time.seconds())?;
+
+    let amount = state.reward_dust;
+    if amount.is_zero() {
+        return Err(ContractError::NoDust {});
     }
-    Ok(())
+    state.reward_dust = Uint128::zero();
+
+    STATE.save(deps.storage, &state)?;
+
+    let reward_msg = token_transfer_msg(&config.reward_token, recipient.clone(), amount)?;
+
+    Ok(Response::new().add_message(reward_msg).add_attributes(vec![
+        ("action", "sweep_dust"),
+        ("recipient", recipient.as_str()),
+        ("amount", amount.to_string().as_str()),
+    ]))
 }
 
-pub fn assert_new_schedules(
-    config: &Config,
-    state: &State,
-    distribution_schedule: Vec<(u64, u64, Uint128)>,
-) -> Result<(), ContractError> {
-    if distribution_schedule.len() < config.distribution_schedule.len() {
-        return Err(ContractError::NotIncludeAllDistributionSchedule {});
+pub fn sweep(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    denom: Denom,
+    recipient: String,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    authcheck(deps.as_ref(), &info)?;
+    deps.api.addr_validate(&recipient)?;
+
+    // principal, unbonding, pending and future rewards stay in the contract
+    let solvency = query_solvency(deps.as_ref(), env.clone())?;
+    let mut owed = Uint128::zero();
+    if denom == Denom::Cw20(Addr::unchecked(&config.lp_token_contract)) {
+        owed = owed.checked_add(solvency.lp_required)?;
+    }
+    if denom == config.reward_token {
+        owed = owed.checked_add(solvency.reward_required)?;
     }
 
-    let mut existing_counts: BTreeMap<(u64, u64, Uint128), u32> = BTreeMap::new();
-    for schedule in config.distribution_schedule.clone() {
-        let counter = existing_counts.entry(schedule).or_insert(0);
-        *counter += 1;
+    let balance = query_token_balance(&deps.querier, &denom, env.contract.address.as_str())?;
+    let amount = balance.saturating_sub(owed);
+    if amount.is_zero() {
+        return Err(ContractError::NothingToSweep {});
     }
 
-    let mut new_counts: BTreeMap<(u64, u64, Uint128), u32> = BTreeMap::new();
-    for schedule in distribution_schedule {
-        let counter = new_counts.entry(schedule).or_insert(0);
-        *counter += 1;
+    let msg = token_transfer_msg(&denom, recipient.clone(), amount)?;
+
+    Ok(Response::new().add_message(msg).add_attributes(vec![
+        ("action", "sweep"),
+        ("recipient", recipient.as_str()),
+        ("amount", amount.to_string().as_str()),
+    ]))
+}
+
+pub fn update_treasury(
+    deps: DepsMut,
+    info: MessageInfo,
+    treasury: String,
+) -> Result<Response, ContractError> {
+    let mut config = CONFIG.load(deps.storage)?;
+    deps.api.addr_validate(&treasury)?;
+
+    authcheck(deps.as_ref(), &info)?;
+    config.treasury = Some(treasury);
+
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new().add_attributes(vec![("action", "update_treasury")]))
+}
+
+pub fn update_idle_reward_policy(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    idle_reward_policy: IdleRewardPolicy,
+) -> Result<Response, ContractError> {
+    let mut config = CONFIG.load(deps.storage)?;
+    let mut state = STATE.load(deps.storage)?;
+
+    authcheck(deps.as_ref(), &info)?;
+    if idle_reward_policy == IdleRewardPolicy::Treasury && config.treasury.is_none() {
+        return Err(ContractError::TreasuryNotSet {});
     }
 
-    for (schedule, count) in existing_counts.into_iter() {
-        // if began ensure its in the new schedule
-        if schedule.0 <= state.last_distributed {
-            if count > *new_counts.get(&schedule).unwrap_or(&0u32) {
-                return Err(ContractError::NewScheduleRemovePastDistribution {});
-            }
-            // after this new_counts will only contain the newly added schedules
-            *new_counts.get_mut(&schedule).unwrap() -= count;
+    // settle under the old policy before switching
+    compute_reward(&config, &mut state, env.block.time.seconds())?;
+    config.idle_reward_policy = idle_reward_policy;
+    let stranded_msgs = take_stranded_reward(&config, &mut state)?;
+
+    CONFIG.save(deps.storage, &config)?;
+    STATE.save(deps.storage, &state)?;
+
+    Ok(Response::new()
+        .add_messages(stranded_msgs)
+        .add_attributes(vec![("action", "update_idle_reward_policy")]))
+}
+
+pub fn update_reward_fee(
+    deps: DepsMut,
+    info: MessageInfo,
+    reward_fee_bps: u64,
+) -> Result<Response, ContractError> {
+    let mut config = CONFIG.load(deps.storage)?;
+
+    authcheck(deps.as_ref(), &info)?;
+    assert_valid_fee(reward_fee_bps, &config.treasury)?;
+    config.reward_fee_bps = reward_fee_bps;
+
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "update_reward_fee"),
+        ("reward_fee_bps", reward_fee_bps.to_string().as_str()),
+    ]))
+}
+
+pub fn update_deposit_fee(
+    deps: DepsMut,
+    info: MessageInfo,
+    deposit_fee_bps: u64,
+) -> Result<Response, ContractError> {
+    let mut config = CONFIG.load(deps.storage)?;
+
+    authcheck(deps.as_ref(), &info)?;
+    assert_valid_fee(deposit_fee_bps, &config.treasury)?;
+    config.deposit_fee_bps = deposit_fee_bps;
+
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "update_deposit_fee"),
+        ("deposit_fee_bps", deposit_fee_bps.to_string().as_str()),
+    ]))
+}
+
+pub fn update_min_bond_amount(
+    deps: DepsMut,
+    info: MessageInfo,
+    min_bond_amount: Uint128,
+) -> Result<Response, ContractError> {
+    let mut config = CONFIG.load(deps.storage)?;
+
+    authcheck(deps.as_ref(), &info)?;
+    config.min_bond_amount = min_bond_amount;
+
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "update_min_bond_amount"),
+        ("min_bond_amount", min_bond_amount.to_string().as_str()),
+    ]))
+}
+
+pub fn update_bond_caps(
+    deps: DepsMut,
+    info: MessageInfo,
+    max_total_bond: Option<Uint128>,
+    max_bond_per_address: Option<Uint128>,
+) -> Result<Response, ContractError> {
+    let mut config = CONFIG.load(deps.storage)?;
+
+    authcheck(deps.as_ref(), &info)?;
+    config.max_total_bond = max_total_bond;
+    config.max_bond_per_address = max_bond_per_address;
+
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new().add_attributes(vec![("action", "update_bond_caps")]))
+}
+
+pub fn update_allowlist_mode(
+    deps: DepsMut,
+    info: MessageInfo,
+    allowlist_mode: AllowlistMode,
+) -> Result<Response, ContractError> {
+    let mut config = CONFIG.load(deps.storage)?;
+
+    authcheck(deps.as_ref(), &info)?;
+    assert_valid_allowlist_mode(deps.as_ref(), &allowlist_mode)?;
+    config.allowlist_mode = allowlist_mode;
+
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new().add_attributes(vec![("action", "update_allowlist_mode")]))
+}
+
+pub fn update_allowlist(
+    deps: DepsMut,
+    info: MessageInfo,
+    addresses: Vec<String>,
+    allowed: bool,
+) -> Result<Response, ContractError> {
+    authcheck(deps.as_ref(), &info)?;
+
+    for address in addresses.iter() {
+        deps.api.addr_validate(address)?;
+        if allowed {
+            ALLOWLIST.save(deps.storage, address.clone(), &true)?;
+        } else {
+            ALLOWLIST.remove(deps.storage, address.clone());
         }
     }
 
-    for (schedule, count) in new_counts.into_iter() {
-        if count > 0 && schedule.0 <= state.last_distributed {
-            return Err(ContractError::NewScheduleAddPastDistribution {});
-        }
+    let action = if allowed {
+        "add_to_allowlist"
+    } else {
+        "remove_from_allowlist"
+    };
+    Ok(Response::new().add_attributes(vec![
+        ("action", action),
+        ("addresses", addresses.join(",").as_str()),
+    ]))
+}
+
+fn assert_valid_allowlist_mode(
+    deps: Deps,
+    allowlist_mode: &AllowlistMode,
+) -> Result<(), ContractError> {
+    if let AllowlistMode::Cw4Group { group } = allowlist_mode {
+        deps.api.addr_validate(group)?;
     }
     Ok(())
 }
 
-// compute distributed rewards and update global reward index
-pub fn compute_reward(config: &Config, state: &mut State, block_time: u64) {
-    if state.total_bond_amount.is_zero() {
-        state.last_distributed = block_time;
-        return;
+// only checked on bond, so removed stakers can still unbond and redeem
+pub fn assert_allowlisted(deps: Deps, config: &Config, address: &str) -> Result<(), ContractError> {
+    let allowed = match &config.allowlist_mode {
+        AllowlistMode::Disabled => true,
+        AllowlistMode::Internal => ALLOWLIST.has(deps.storage, address.to_string()),
+        AllowlistMode::Cw4Group { group } => Cw4Contract(Addr::unchecked(group))
+            .member_at_height(&deps.querier, address.to_string(), None)?
+            .is_some(),
+    };
+    if !allowed {
+        return Err(ContractError::NotAllowlisted {});
     }
+    Ok(())
+}
 
-    let mut distributed_amount: Uint128 = Uint128::zero();
-    for s in config.distribution_schedule.iter() {
-        if s.0 > block_time || s.1 < state.last_distributed {
-            continue;
-        }
+pub fn add_hook(deps: DepsMut, info: MessageInfo, addr: String) -> Result<Response, ContractError> {
+    authcheck(deps.as_ref(), &info)?;
+    HOOKS.add_hook(deps.storage, deps.api.addr_validate(&addr)?)?;
+
+    Ok(Response::new().add_attributes(vec![("action", "add_hook"), ("hook", addr.as_str())]))
+}
 
-        // min(s.1, block_time) - max(s.0, last_distributed)
-        let passed_time =
-            std::cmp::min(s.1, block_time) - std::cmp::max(s.0, state.last_distributed);
+pub fn update_voting_config(
+    deps: DepsMut,
+    info: MessageInfo,
+    dao: Option<String>,
+    count_unbonding_power: bool,
+) -> Result<Response, ContractError> {
+    let mut config = CONFIG.load(deps.storage)?;
 
-        let time = s.1 - s.0;
-        let distribution_amount_per_second: Decimal = Decimal::from_ratio(s.2, time);
-        distributed_amount += distribution_amount_per_second * Uint128::from(passed_time as u128);
+    authcheck(deps.as_ref(), &info)?;
+    if let Some(dao) = &dao {
+        deps.api.addr_validate(dao)?;
     }
+    config.dao = dao;
+    config.count_unbonding_power = count_unbonding_power;
 
-    state.last_distributed = block_time;
-    state.global_reward_index = state.global_reward_index
-        + Decimal::from_ratio(distributed_amount, state.total_bond_amount);
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new().add_attributes(vec![("action", "update_voting_config")]))
 }
 
-// withdraw reward to pending reward
-pub fn compute_staker_reward(state: &State, staker_info: &mut StakerInfo) -> StdResult<()> {
-    let pending_reward = (staker_info.bond_amount * state.global_reward_index)
-        .checked_sub(staker_info.bond_amount * staker_info.reward_index)?;
+pub fn update_reward_vesting_duration(
+    deps: DepsMut,
+    info: MessageInfo,
+    reward_vesting_duration: u64,
+) -> Result<Response, ContractError> {
+    let mut config = CONFIG.load(deps.storage)?;
+
+    authcheck(deps.as_ref(), &info)?;
+    config.reward_vesting_duration = reward_vesting_duration;
 
-    staker_info.reward_index = state.global_reward_index;
-    staker_info.pending_reward += pending_reward;
-    Ok(())
-}
+    CONFIG.save(deps.storage, &config)?;
 
-fn increase_bond_amount(state: &mut State, staker_info: &mut StakerInfo, amount: Uint128) {
-    state.total_bond_amount += amount;
-    staker_info.bond_amount += amount;
+    Ok(Response::new().add_attributes(vec![("action", "update_reward_vesting_duration")]))
 }
 
-fn decrease_bond_amount(
-    state: &mut State,
-    staker_info: &mut StakerInfo,
-    amount: Uint128,
-) -> StdResult<()> {
-    state.total_bond_amount = state.total_bond_amount.checked_sub(amount)?;
-    staker_info.bond_amount = staker_info.bond_amount.checked_sub(amount)?;
-    Ok(())
+pub fn update_loyalty_multipliers(
+    deps: DepsMut,
+    info: MessageInfo,
+    loyalty_multipliers: Vec<(u64, Decimal256)>,
+) -> Result<Response, ContractError> {
+    let mut config = CONFIG.load(deps.storage)?;
+
+    authcheck(deps.as_ref(), &info)?;
+    assert_valid_loyalty_multipliers(&loyalty_multipliers)?;
+    config.loyalty_multipliers = loyalty_multipliers;
+
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new().add_attributes(vec![("action", "update_loyalty_multipliers")]))
 }
 
-#[cfg_attr(not(feature = "library"), entry_point)]
-pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
+// anyone may refresh a staker, the tiers only grow with time
+pub fn refresh_loyalty(deps: DepsMut, env: Env, staker: String) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let mut state = STATE.load(deps.storage)?;
+    let staker_info_key = staker_info_key(&staker);
+    let mut staker_info = staker_info_storage()
+        .may_load(deps.storage, staker_info_key.clone())?
+        .ok_or(ContractError::NotStaked {})?;
+
+    let time = env.block.time.seconds();
+    compute_reward(&config, &mut state, time)?;
+    compute_staker_reward(&config, &state, &mut staker_info)?;
+    update_reward_weight(&config, &mut state, &mut staker_info, time)?;
+
+    credit_referral_reward(deps.storage, &mut state, &mut staker_info)?;
+    staker_info_storage().save(deps.storage, staker_info_key, &staker_info)?;
+    STATE.save(deps.storage, &state)?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "refresh_loyalty"),
+        ("staker", staker.as_str()),
+        (
+            "reward_weight",
+            staker_info.reward_weight.to_string().as_str(),
+        ),
+    ]))
+}
+
+pub fn update_nft_boosts(
+    deps: DepsMut,
+    info: MessageInfo,
+    nft_boosts: Vec<(String, Decimal256)>,
+) -> Result<Response, ContractError> {
+    let mut config = CONFIG.load(deps.storage)?;
+
+    authcheck(deps.as_ref(), &info)?;
+    assert_valid_nft_boosts(deps.as_ref(), &nft_boosts)?;
+    config.nft_boosts = nft_boosts;
+
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new().add_attributes(vec![("action", "update_nft_boosts")]))
+}
+
+pub fn update_referral_fee(
+    deps: DepsMut,
+    info: MessageInfo,
+    referral_fee_bps: u64,
+) -> Result<Response, ContractError> {
+    let mut config = CONFIG.load(deps.storage)?;
+
+    authcheck(deps.as_ref(), &info)?;
+    assert_valid_referral_fee(referral_fee_bps)?;
+    config.referral_fee_bps = referral_fee_bps;
+
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "update_referral_fee"),
+        ("referral_fee_bps", referral_fee_bps.to_string().as_str()),
+    ]))
+}
+
+pub fn remove_hook(
+    deps: DepsMut,
+    info: MessageInfo,
+    addr: String,
+) -> Result<Response, ContractError> {
+    authcheck(deps.as_ref(), &info)?;
+    HOOKS.remove_hook(deps.storage, deps.api.addr_validate(&addr)?)?;
+
+    Ok(Response::new().add_attributes(vec![("action", "remove_hook"), ("hook", addr.as_str())]))
+}
+
+// notify every registered hook of a stake change
+fn stake_changed_hook_msgs(
+    storage: &dyn Storage,
+    msg: StakeChangedHookMsg,
+) -> StdResult<Vec<SubMsg>> {
+    HOOKS.prepare_hooks(storage, |hook| {
+        Ok(SubMsg::new(msg.clone().into_cosmos_msg(hook)?))
+    })
+}
+
+// mirror the bond of a staker into the voting power snapshots and its cw4 member weight
+fn snapshot_bond(
+    storage: &mut dyn Storage,
+    config: &Config,
+    height: u64,
+    state: &State,
+    staker_info: &StakerInfo,
+) -> Result<(), ContractError> {
+    let addr = Addr::unchecked(&staker_info.address);
+    BONDED_BALANCES.save(storage, &addr, &staker_info.shares, height)?;
+    BONDED_TOTAL.save(storage, &state.total_shares, height)?;
+    update_member_weight(storage, config, height, staker_info)
+}
+
+// mirror the shares of a staker into its cw4 member weight
+fn update_member_weight(
+    storage: &mut dyn Storage,
+    config: &Config,
+    height: u64,
+    staker_info: &StakerInfo,
+) -> Result<(), ContractError> {
+    let addr = Addr::unchecked(&staker_info.address);
+    let old_weight = MEMBERS.may_load(storage, &addr)?.unwrap_or_default();
+    let new_weight = member_weight(config, staker_info.shares)?;
+    if old_weight == new_weight {
+        return Ok(());
+    }
+
+    if new_weight == 0 {
+        MEMBERS.remove(storage, &addr, height)?;
+    } else {
+        MEMBERS.save(storage, &addr, &new_weight, height)?;
+    }
+
+    let total = TOTAL_WEIGHT
+        .may_load(storage)?
+        .unwrap_or_default()
+        .checked_sub(Uint128::from(old_weight))?
+        .checked_add(Uint128::from(new_weight))?;
+    TOTAL_WEIGHT.save(storage, &total)?;
+    TOTAL.save(storage, &u64::try_from(total.u128()).unwrap_or(u64::MAX))?;
+    Ok(())
+}
+
+// cw4 weights are u64, so bonds too large for the divisor count as u64::MAX
+// rather than blocking the bond
+fn member_weight(config: &Config, bond_amount: Uint128) -> Result<u64, ContractError> {
+    let weight = bond_amount.checked_div(config.weight_divisor.unwrap_or(Uint128::new(1)))?;
+    Ok(u64::try_from(weight.u128()).unwrap_or(u64::MAX))
+}
+
+// fees are capped and need a treasury to be sent to
+fn assert_valid_fee(fee_bps: u64, treasury: &Option<String>) -> Result<(), ContractError> {
+    if fee_bps > MAX_FEE_BPS {
+        return Err(ContractError::FeeTooHigh {
+            max_bps: MAX_FEE_BPS,
+        });
+    }
+    if fee_bps > 0 && treasury.is_none() {
+        return Err(ContractError::TreasuryNotSet {});
+    }
+    Ok(())
+}
+
+// part of a reward withdrawal of `amount` sent to the treasury
+pub fn reward_fee(config: &Config, amount: Uint128) -> Uint128 {
+    amount.multiply_ratio(config.reward_fee_bps, FEE_BPS_DENOMINATOR)
+}
+
+// part of a bond of `amount` sent to the treasury
+fn deposit_fee(config: &Config, amount: Uint128) -> Uint128 {
+    amount.multiply_ratio(config.deposit_fee_bps, FEE_BPS_DENOMINATOR)
+}
+
+// under the treasury policy, hand the stranded rewards over to the treasury
+fn take_stranded_reward(config: &Config, state: &mut State) -> StdResult<Vec<CosmosMsg>> {
+    let treasury = match (&config.idle_reward_policy, &config.treasury) {
+        (IdleRewardPolicy::Treasury, Some(treasury)) => treasury.clone(),
+        _ => return Ok(vec![]),
+    };
+    if state.stranded_reward.is_zero() {
+        return Ok(vec![]);
+    }
+
+    let amount = state.stranded_reward;
+    state.stranded_reward = Uint128::zero();
+    Ok(vec![token_transfer_msg(
+        &config.reward_token,
+        treasury,
+        amount,
+    )?])
+}
+
+fn authcheck(deps: Deps, info: &MessageInfo) -> Result<(), ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    if info.sender != config.admin {
+        return Err(ContractError::Unauthorized {});
+    }
+    Ok(())
+}
+
+pub fn assert_new_schedules(
+    config: &Config,
+    state: &State,
+    distribution_schedule: Vec<(u64, u64, Uint128)>,
+) -> Result<(), ContractError> {
+    if distribution_schedule.len() < config.distribution_schedule.len() {
+        return Err(ContractError::NotIncludeAllDistributionSchedule {});
+    }
+
+    let mut existing_counts: BTreeMap<(u64, u64, Uint128), u32> = BTreeMap::new();
+    for schedule in config.distribution_schedule.clone() {
+        let counter = existing_counts.entry(schedule).or_insert(0);
+        *counter += 1;
+    }
+
+    let mut new_counts: BTreeMap<(u64, u64, Uint128), u32> = BTreeMap::new();
+    for schedule in distribution_schedule {
+        let counter = new_counts.entry(schedule).or_insert(0);
+        *counter += 1;
+    }
+
+    for (schedule, count) in existing_counts.into_iter() {
+        // if began ensure its in the new schedule
+        if schedule.0 <= state.last_distributed {
+            if count > *new_counts.get(&schedule).unwrap_or(&0u32) {
+                return Err(ContractError::NewScheduleRemovePastDistribution {});
+            }
+            // after this new_counts will only contain the newly added schedules
+            *new_counts.get_mut(&schedule).unwrap() -= count;
+        }
+    }
+
+    for (schedule, count) in new_counts.into_iter() {
+        if count > 0 && schedule.0 <= state.last_distributed {
+            return Err(ContractError::NewScheduleAddPastDistribution {});
+        }
+    }
+    Ok(())
+}
+
+// state transition of `bond`, shared with the SimulateBond query;
+// returns the deposit fee taken out of `amount`
+pub fn apply_bond(
+    config: &Config,
+    state: &mut State,
+    staker_info: &mut StakerInfo,
+    amount: Uint128,
+    block_time: u64,
+) -> Result<Uint128, ContractError> {
+    let fee = deposit_fee(config, amount);
+    let amount = amount.checked_sub(fee)?;
+    if let Some(max_total_bond) = config.max_total_bond {
+        let remaining = max_total_bond.saturating_sub(state.total_bond_amount);
+        if amount > remaining {
+            return Err(ContractError::TotalBondCapExceeded { remaining });
+        }
+    }
+    assert_position_limits(config, staker_info, amount)?;
+
+    compute_reward(config, state, block_time)?;
+    compute_staker_reward(config, state, staker_info)?;
+    let shares = staker_info.shares;
+    increase_bond_amount(state, staker_info, amount)?;
+    update_loyalty(config, state, staker_info, shares, block_time)?;
+    Ok(fee)
+}
+
+// minimum bond and per address cap of a position growing by `amount`
+fn assert_position_limits(
+    config: &Config,
+    staker_info: &StakerInfo,
+    amount: Uint128,
+) -> Result<(), ContractError> {
+    if staker_info.bond_amount.checked_add(amount)? < config.min_bond_amount {
+        return Err(ContractError::BelowMinimumBond {
+            min_bond_amount: config.min_bond_amount,
+        });
+    }
+    if let Some(max_bond_per_address) = config.max_bond_per_address {
+        let remaining = max_bond_per_address.saturating_sub(staker_info.bond_amount);
+        if amount > remaining {
+            return Err(ContractError::AddressBondCapExceeded { remaining });
+        }
+    }
+    Ok(())
+}
+
+// state transition of `unbond`, shared with the SimulateUnbond query
+pub fn apply_unbond(
+    config: &Config,
+    state: &mut State,
+    staker_info: &mut StakerInfo,
+    amount: Uint128,
+    block_time: u64,
+) -> Result<(), ContractError> {
+    if shares_to_amount(state, staker_info.shares)? < amount {
+        return Err(ContractError::ExceedBondAmount {});
+    }
+
+    compute_reward(config, state, block_time)?;
+    compute_staker_reward(config, state, staker_info)?;
+    let shares = staker_info.shares;
+    decrease_bond_amount(state, staker_info, amount)?;
+    update_loyalty(config, state, staker_info, shares, block_time)
+}
+
+// state transition of `withdraw`, shared with the SimulateWithdraw query;
+// returns the reward taken out of the pending reward
+pub fn apply_withdraw(
+    config: &Config,
+    state: &mut State,
+    staker_info: &mut StakerInfo,
+    block_time: u64,
+) -> Result<Uint128, ContractError> {
+    compute_reward(config, state, block_time)?;
+    compute_staker_reward(config, state, staker_info)?;
+
+    let amount = staker_info.pending_reward;
+    staker_info.pending_reward = Uint128::zero();
+    state.total_owed_reward = state.total_owed_reward.checked_sub(amount)?;
+    Ok(amount)
+}
+
+// compute distributed rewards and update global reward index
+pub fn compute_reward(
+    config: &Config,
+    state: &mut State,
+    block_time: u64,
+) -> Result<(), ContractError> {
+    // nothing to distribute for a moment already accounted for
+    if block_time < state.last_distributed {
+        return Ok(());
+    }
+
+    if state.total_reward_weight.is_zero() {
+        // nobody can earn the scheduled rewards, keep them aside;
+        // continuous emission simply does not draw from the balance
+        if config.emission_mode == EmissionMode::Schedule {
+            state.stranded_reward = state.stranded_reward.checked_add(compute_scheduled_amount(
+                config,
+                state.last_distributed,
+                block_time,
+            )?)?;
+        }
+        state.last_distributed = block_time;
+        return Ok(());
+    }
+
+    let stranded_amount = release_stranded_reward(config, state, block_time)?;
+
+    let distributed_amount = match config.emission_mode {
+        EmissionMode::Schedule => {
+            compute_scheduled_amount(config, state.last_distributed, block_time)?
+        }
+        EmissionMode::Continuous { reward_per_second } => {
+            // emit until the funded balance is exhausted
+            let passed_time = checked_time_sub(block_time, state.last_distributed)?;
+            let emitted = std::cmp::min(
+                reward_per_second.checked_mul(Uint128::from(passed_time))?,
+                state.reward_balance,
+            );
+            state.reward_balance = state.reward_balance.checked_sub(emitted)?;
+            emitted
+        }
+    };
+
+    // fold the remainder left over by previous distributions back in
+    let distributed_amount = distributed_amount
+        .checked_add(stranded_amount)?
+        .checked_add(state.reward_dust)?;
+
+    let total_reward_weight = Uint256::from(state.total_reward_weight);
+    let index_increment = Decimal256::new(
+        Uint256::from(distributed_amount)
+            .checked_mul(index_atomics_per_unit())?
+            .checked_div(total_reward_weight)?,
+    );
+
+    // whatever the truncated index increment cannot pay out is kept as dust;
+    // rounding the assigned part up guarantees dust is never owed to stakers too
+    let assigned_amount = Uint128::try_from(ceil_div(
+        index_increment.atomics().checked_mul(total_reward_weight)?,
+        index_atomics_per_unit(),
+    )?)
+    .map_err(StdError::from)?;
+
+    state.last_distributed = block_time;
+    state.global_reward_index = Decimal256::new(
+        state
+            .global_reward_index
+            .atomics()
+            .checked_add(index_increment.atomics())?,
+    );
+    state.reward_dust = distributed_amount.checked_sub(assigned_amount)?;
+    state.total_owed_reward = state.total_owed_reward.checked_add(assigned_amount)?;
+    Ok(())
+}
+
+// per second emission at `block_time`, following the same rules as `compute_reward`;
+// `state` must already be settled up to `block_time`
+pub fn compute_emission_rate(
+    config: &Config,
+    state: &State,
+    block_time: u64,
+) -> Result<Decimal256, ContractError> {
+    let mut rate = match config.emission_mode {
+        EmissionMode::Schedule => {
+            let mut rate = Decimal256::zero();
+            for s in config.distribution_schedule.iter() {
+                if s.0 > block_time || s.1 <= block_time {
+                    continue;
+                }
+                let slot_rate = Decimal256::from_ratio(s.2, slot_duration(s)?);
+                rate = Decimal256::new(rate.atomics().checked_add(slot_rate.atomics())?);
+            }
+            rate
+        }
+        EmissionMode::Continuous { reward_per_second } => {
+            if state.reward_balance.is_zero() {
+                Decimal256::zero()
+            } else {
+                Decimal256::from_ratio(reward_per_second, 1u8)
+            }
+        }
+    };
+
+    // rolled forward rewards are emitted on top until the schedule ends
+    if config.idle_reward_policy == IdleRewardPolicy::RollForward
+        && !state.stranded_reward.is_zero()
+        && !state.total_reward_weight.is_zero()
+    {
+        let schedule_end = config
+            .distribution_schedule
+            .iter()
+            .map(|s| s.1)
+            .max()
+            .unwrap_or_default();
+        if schedule_end > block_time {
+            let stranded_rate = Decimal256::from_ratio(
+                state.stranded_reward,
+                checked_time_sub(schedule_end, block_time)?,
+            );
+            rate = Decimal256::new(rate.atomics().checked_add(stranded_rate.atomics())?);
+        }
+    }
+
+    Ok(rate)
+}
+
+// release the stranded rewards due to stakers by `block_time`
+fn release_stranded_reward(
+    config: &Config,
+    state: &mut State,
+    block_time: u64,
+) -> Result<Uint128, ContractError> {
+    let released = match config.idle_reward_policy {
+        IdleRewardPolicy::Treasury => Uint128::zero(),
+        IdleRewardPolicy::FirstStaker => state.stranded_reward,
+        IdleRewardPolicy::RollForward => {
+            // linearly until the end of the schedule, at once if it is over
+            let schedule_end = config
+                .distribution_schedule
+                .iter()
+                .map(|s| s.1)
+                .max()
+                .unwrap_or_default();
+            if block_time >= schedule_end || state.last_distributed >= schedule_end {
+                state.stranded_reward
+            } else {
+                state.stranded_reward.multiply_ratio(
+                    checked_time_sub(block_time, state.last_distributed)?,
+                    checked_time_sub(schedule_end, state.last_distributed)?,
+                )
+            }
+        }
+    };
+
+    state.stranded_reward = state.stranded_reward.checked_sub(released)?;
+    Ok(released)
+}
+
+// compute the amount distributed by the schedule between `from` and `to`
+pub fn compute_scheduled_amount(
+    config: &Config,
+    from: u64,
+    to: u64,
+) -> Result<Uint128, ContractError> {
+    let mut distributed_amount: Uint128 = Uint128::zero();
+    for s in config.distribution_schedule.iter() {
+        if s.0 > to || s.1 < from {
+            continue;
+        }
+
+        let time = slot_duration(s)?;
+        // emitted(min(s.1, to)) - emitted(max(s.0, from)), so that truncation
+        // does not accumulate across calls and each slot emits exactly s.2
+        let emitted_until = |t: u64| -> Result<Uint128, ContractError> {
+            Ok(s.2.multiply_ratio(checked_time_sub(t, s.0)?, time))
+        };
+        distributed_amount = distributed_amount.checked_add(
+            emitted_until(std::cmp::min(s.1, to))?
+                .checked_sub(emitted_until(std::cmp::max(s.0, from))?)?,
+        )?;
+    }
+    Ok(distributed_amount)
+}
+
+fn slot_duration(slot: &(u64, u64, Uint128)) -> Result<u64, ContractError> {
+    match slot.1.checked_sub(slot.0) {
+        Some(time) if time > 0 => Ok(time),
+        _ => Err(ContractError::InvalidSchedule {
+            start: slot.0,
+            end: slot.1,
+        }),
+    }
+}
+
+fn assert_valid_loyalty_multipliers(
+    loyalty_multipliers: &[(u64, Decimal256)],
+) -> Result<(), ContractError> {
+    let ascending = loyalty_multipliers
+        .windows(2)
+        .all(|tiers| tiers[0].0 < tiers[1].0);
+    if !ascending
+        || loyalty_multipliers
+            .iter()
+            .any(|tier| tier.1 < Decimal256::one())
+    {
+        return Err(ContractError::InvalidLoyaltyMultipliers {});
+    }
+    Ok(())
+}
+
+fn assert_valid_referral_fee(referral_fee_bps: u64) -> Result<(), ContractError> {
+    if referral_fee_bps > MAX_FEE_BPS {
+        return Err(ContractError::FeeTooHigh {
+            max_bps: MAX_FEE_BPS,
+        });
+    }
+    Ok(())
+}
+
+fn assert_valid_nft_boosts(
+    deps: Deps,
+    nft_boosts: &[(String, Decimal256)],
+) -> Result<(), ContractError> {
+    for (collection, boost) in nft_boosts {
+        deps.api.addr_validate(collection)?;
+        if boost.is_zero() {
+            return Err(ContractError::InvalidNftBoosts {});
+        }
+    }
+    Ok(())
+}
+
+pub fn assert_valid_schedule(
+    distribution_schedule: &[(u64, u64, Uint128)],
+) -> Result<(), ContractError> {
+    for slot in distribution_schedule {
+        slot_duration(slot)?;
+    }
+    Ok(())
+}
+
+fn checked_time_sub(a: u64, b: u64) -> Result<u64, ContractError> {
+    a.checked_sub(b)
+        .ok_or_else(|| OverflowError::new(OverflowOperation::Sub, a, b).into())
+}
+
+// Decimal256 atomics carry another 10^18 on top of REWARD_INDEX_SCALE
+fn index_atomics_per_unit() -> Uint256 {
+    Uint256::from(REWARD_INDEX_SCALE) * Uint256::from(REWARD_INDEX_SCALE)
+}
+
+// rewards accrued by `reward_weight` over the reward index
+fn reward_for_index(reward_weight: Uint128, index: Decimal256) -> Result<Uint256, ContractError> {
+    let reward = Uint256::from(reward_weight)
+        .full_mul(index.atomics())
+        .checked_div(Uint512::from(index_atomics_per_unit()))?;
+    Ok(Uint256::try_from(reward).map_err(StdError::from)?)
+}
+
+fn ceil_div(numerator: Uint256, denominator: Uint256) -> Result<Uint256, ContractError> {
+    let quotient = numerator.checked_div(denominator)?;
+    if quotient.checked_mul(denominator)? == numerator {
+        Ok(quotient)
+    } else {
+        Ok(quotient.checked_add(Uint256::from(1u8))?)
+    }
+}
+
+// withdraw reward to pending reward, less the referrer's cut
+pub fn compute_staker_reward(
+    config: &Config,
+    state: &State,
+    staker_info: &mut StakerInfo,
+) -> Result<(), ContractError> {
+    let pending_reward = Uint128::try_from(
+        reward_for_index(staker_info.reward_weight, state.global_reward_index)?.checked_sub(
+            reward_for_index(staker_info.reward_weight, staker_info.reward_index)?,
+        )?,
+    )
+    .map_err(StdError::from)?;
+
+    let referral_reward = match staker_info.referrer {
+        Some(_) => pending_reward.multiply_ratio(config.referral_fee_bps, FEE_BPS_DENOMINATOR),
+        None => Uint128::zero(),
+    };
+
+    staker_info.reward_index = state.global_reward_index;
+    staker_info.pending_reward = staker_info
+        .pending_reward
+        .checked_add(pending_reward.checked_sub(referral_reward)?)?;
+    staker_info.referral_reward = staker_info.referral_reward.checked_add(referral_reward)?;
+    Ok(())
+}
+
+// move the referrer's cut of the staker's rewards to the referrer's earnings
+fn credit_referral_reward(
+    storage: &mut dyn Storage,
+    state: &mut State,
+    staker_info: &mut StakerInfo,
+) -> Result<(), ContractError> {
+    let referrer = match &staker_info.referrer {
+        Some(referrer) if !staker_info.referral_reward.is_zero() => Addr::unchecked(referrer),
+        _ => return Ok(()),
+    };
+
+    let amount = staker_info.referral_reward;
+    let mut earnings = REFERRAL_EARNINGS
+        .may_load(storage, &referrer)?
+        .unwrap_or_default();
+    earnings.pending = earnings.pending.checked_add(amount)?;
+    earnings.total = earnings.total.checked_add(amount)?;
+    REFERRAL_EARNINGS.save(storage, &referrer, &earnings)?;
+    state.total_referral_reward = state.total_referral_reward.checked_add(amount)?;
+    state.total_owed_reward = state.total_owed_reward.checked_sub(amount)?;
+    staker_info.referral_reward = Uint128::zero();
+    Ok(())
+}
+
+fn record_referral(
+    storage: &mut dyn Storage,
+    api: &dyn Api,
+    staker: &str,
+    referrer: &str,
+) -> Result<String, ContractError> {
+    let referrer = api.addr_validate(referrer)?;
+    if referrer == staker {
+        return Err(ContractError::InvalidReferrer {});
+    }
+    REFERRALS.save(storage, (&referrer, &Addr::unchecked(staker)), &Empty {})?;
+    Ok(referrer.to_string())
+}
+
+// closing a position ends its referral
+fn remove_staker_info(
+    storage: &mut dyn Storage,
+    key: String,
+    staker_info: &StakerInfo,
+) -> StdResult<()> {
+    if let Some(referrer) = &staker_info.referrer {
+        REFERRALS.remove(
+            storage,
+            (
+                &Addr::unchecked(referrer),
+                &Addr::unchecked(&staker_info.address),
+            ),
+        );
+    }
+    staker_info_storage().remove(storage, key)
+}
+
+pub fn increase_bond_amount(
+    state: &mut State,
+    staker_info: &mut StakerInfo,
+    amount: Uint128,
+) -> Result<(), ContractError> {
+    let shares = amount_to_shares(state, amount, false)?;
+    state.total_bond_amount = state.total_bond_amount.checked_add(amount)?;
+    state.total_shares = state.total_shares.checked_add(shares)?;
+    staker_info.bond_amount = staker_info.bond_amount.checked_add(amount)?;
+    staker_info.shares = staker_info.shares.checked_add(shares)?;
+    Ok(())
+}
+
+fn decrease_bond_amount(
+    state: &mut State,
+    staker_info: &mut StakerInfo,
+    amount: Uint128,
+) -> Result<(), ContractError> {
+    let shares = amount_to_shares(state, amount, true)?;
+    // a position kept for its pending reward or NFTs has nothing left to unbond
+    if staker_info.shares.is_zero() || shares > staker_info.shares {
+        return Err(ContractError::ExceedBondAmount {});
+    }
+    // compounding lets the shares be worth more than the bonded principal,
+    // so the principal leaves with the shares rather than the LP
+    let principal = staker_info
+        .bond_amount
+        .multiply_ratio(shares, staker_info.shares);
+    state.total_bond_amount = state.total_bond_amount.checked_sub(amount)?;
+    state.total_shares = state.total_shares.checked_sub(shares)?;
+    staker_info.shares = staker_info.shares.checked_sub(shares)?;
+    staker_info.bond_amount = staker_info.bond_amount.checked_sub(principal)?;
+    Ok(())
+}
+
+// multiplier of the highest loyalty tier `staker_info` has reached by `block_time`
+pub fn loyalty_multiplier(
+    config: &Config,
+    staker_info: &StakerInfo,
+    block_time: u64,
+) -> Decimal256 {
+    let staked_duration = block_time.saturating_sub(staker_info.staked_since);
+    config
+        .loyalty_multipliers
+        .iter()
+        .take_while(|tier| tier.0 <= staked_duration)
+        .last()
+        .map(|tier| tier.1)
+        .unwrap_or_else(Decimal256::one)
+}
+
+// after the shares of a settled staker changed from `previous_shares`, scale its
+// staking age by the shares kept: a fresh bond dilutes it, an unbond takes away
+// the unbonded part; then reweight the staker with the resulting multiplier
+pub fn update_loyalty(
+    config: &Config,
+    state: &mut State,
+    staker_info: &mut StakerInfo,
+    previous_shares: Uint128,
+    block_time: u64,
+) -> Result<(), ContractError> {
+    let kept_shares = previous_shares.min(staker_info.shares);
+    let total_shares = previous_shares.max(staker_info.shares);
+    let age = if total_shares.is_zero() {
+        Uint128::zero()
+    } else {
+        Uint128::from(block_time.saturating_sub(staker_info.staked_since))
+            .multiply_ratio(kept_shares, total_shares)
+    };
+    staker_info.staked_since = block_time.saturating_sub(age.u128() as u64);
+    update_reward_weight(config, state, staker_info, block_time)
+}
+
+// settle rewards before reweighting, past rewards follow the previous weight
+fn update_reward_weight(
+    config: &Config,
+    state: &mut State,
+    staker_info: &mut StakerInfo,
+    block_time: u64,
+) -> Result<(), ContractError> {
+    let nft_multiplier = Decimal256::new(
+        Decimal256::one()
+            .atomics()
+            .checked_add(staker_info.nft_boost.atomics())?,
+    );
+    let multiplier =
+        loyalty_multiplier(config, staker_info, block_time).checked_mul(nft_multiplier)?;
+    let reward_weight = Uint128::try_from(
+        Uint256::from(staker_info.shares)
+            .checked_mul(multiplier.atomics())?
+            .checked_div(Decimal256::one().atomics())?,
+    )
+    .map_err(StdError::from)?;
+
+    state.total_reward_weight = state
+        .total_reward_weight
+        .checked_sub(staker_info.reward_weight)?
+        .checked_add(reward_weight)?;
+    staker_info.reward_weight = reward_weight;
+    Ok(())
+}
+
+// release the part of the locked rewards vested by `block_time`
+pub fn settle_vesting(vesting: &mut VestingInfo, block_time: u64) -> StdResult<()> {
+    let now = block_time.min(vesting.end_time);
+    if now <= vesting.start_time {
+        return Ok(());
+    }
+    let released = vesting.locked_amount.multiply_ratio(
+        now - vesting.start_time,
+        vesting.end_time - vesting.start_time,
+    );
+    vesting.locked_amount = vesting.locked_amount.checked_sub(released)?;
+    vesting.vested_amount = vesting.vested_amount.checked_add(released)?;
+    vesting.start_time = now;
+    Ok(())
+}
+
+// LP backing `shares`, rounded down
+pub fn shares_to_amount(state: &State, shares: Uint128) -> StdResult<Uint128> {
+    if state.total_shares.is_zero() {
+        return Ok(Uint128::zero());
+    }
+    Ok(shares.multiply_ratio(state.total_bond_amount, state.total_shares))
+}
+
+// shares backed by `amount` of LP; minting rounds down and burning rounds up
+// so that the pool never pays out more than it holds
+fn amount_to_shares(
+    state: &State,
+    amount: Uint128,
+    round_up: bool,
+) -> Result<Uint128, ContractError> {
+    if state.total_shares.is_zero() || state.total_bond_amount.is_zero() {
+        return Ok(amount);
+    }
+    let numerator = Uint256::from(amount).checked_mul(Uint256::from(state.total_shares))?;
+    let denominator = Uint256::from(state.total_bond_amount);
+    let shares = if round_up {
+        ceil_div(numerator, denominator)?
+    } else {
+        numerator.checked_div(denominator)?
+    };
+    Ok(Uint128::try_from(shares).map_err(StdError::from)?)
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn migrate(deps: DepsMut, env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
     let version = get_contract_version(deps.storage)?;
     if version.contract != CONTRACT_NAME {
         return Err(ContractError::CannotMigrate {
             previous_contract: version.contract,
         });
     }
-    if version.version != CONTRACT_VERSION {
-        return Err(ContractError::CannotMigrate {
-            previous_contract: version.contract,
-        });
+    match version.version.as_str() {
+        // reward indices were plain `Decimal`s without `REWARD_INDEX_SCALE`
+        "0.1.0" => {
+            migrate_reward_indices(deps.storage)?;
+            migrate_shares(deps.storage, env.block.time.seconds())?;
+            migrate_unbonding_info(deps.storage, env.block.height)?;
+            migrate_members(deps.storage, env.block.height)?;
+            migrate_owed_reward(deps.storage)?;
+        }
+        CONTRACT_VERSION => {}
+        _ => {
+            return Err(ContractError::CannotMigrate {
+                previous_contract: version.contract,
+            })
+        }
     }
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
     Ok(Response::default())
 }
+
+fn migrate_reward_indices(storage: &mut dyn Storage) -> Result<(), ContractError> {
+    let scale = Decimal256::from_ratio(REWARD_INDEX_SCALE, 1u128);
+
+    let mut state = STATE.load(storage)?;
+    state.global_reward_index = state.global_reward_index.checked_mul(scale)?;
+    STATE.save(storage, &state)?;
+
+    let stakers = staker_info_storage()
+        .range(storage, None, None, Order::Ascending)
+        .map(|res| res.map(|item| item.1))
+        .collect::<StdResult<Vec<_>>>()?;
+    for mut staker_info in stakers {
+        staker_info.reward_index = staker_info.reward_index.checked_mul(scale)?;
+        staker_info_storage().save(storage, staker_info_key(&staker_info.address), &staker_info)?;
+    }
+    Ok(())
+}
+
+// give every staker one share and one unit of reward weight per bonded unit,
+// counting its loyalty from the migration on
+fn migrate_shares(storage: &mut dyn Storage, block_time: u64) -> Result<(), ContractError> {
+    let mut state = STATE.load(storage)?;
+    state.total_shares = state.total_bond_amount;
+    state.total_reward_weight = state.total_bond_amount;
+    STATE.save(storage, &state)?;
+
+    let stakers = staker_info_storage()
+        .range(storage, None, None, Order::Ascending)
+        .map(|res| res.map(|item| item.1))
+        .collect::<StdResult<Vec<_>>>()?;
+    for mut staker_info in stakers {
+        staker_info.shares = staker_info.bond_amount;
+        staker_info.reward_weight = staker_info.bond_amount;
+        staker_info.staked_since = block_time;
+        staker_info_storage().save(storage, staker_info_key(&staker_info.address), &staker_info)?;
+    }
+    Ok(())
+}
+
+// index unbonding entries by time and total them into `State.total_unbonding_amount`;
+// shares start out equal to LP, so the unbonding power snapshots take the LP amounts
+fn migrate_unbonding_info(storage: &mut dyn Storage, height: u64) -> Result<(), ContractError> {
+    let unbonding_infos = unbonding_info_storage()
+        .range(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+
+    let mut state = STATE.load(storage)?;
+    for (key, unbonding_info) in unbonding_infos {
+        state.total_unbonding_amount = state
+            .total_unbonding_amount
+            .checked_add(unbonding_info.amount)?;
+        UNBONDING_BALANCES.update(
+            storage,
+            &Addr::unchecked(&unbonding_info.address),
+            height,
+            |balance| -> StdResult<_> {
+                Ok(balance
+                    .unwrap_or_default()
+                    .checked_add(unbonding_info.amount)?)
+            },
+        )?;
+        unbonding_info_storage().save(storage, key, &unbonding_info)?;
+    }
+    STATE.save(storage, &state)?;
+    UNBONDING_TOTAL.save(storage, &state.total_unbonding_amount, height)?;
+    Ok(())
+}
+
+// start the running total of owed rewards from what the stakers are owed now
+fn migrate_owed_reward(storage: &mut dyn Storage) -> Result<(), ContractError> {
+    let config = CONFIG.load(storage)?;
+    let mut state = STATE.load(storage)?;
+
+    let mut owed_reward = Uint128::zero();
+    for staker_info in staker_info_storage().range(storage, None, None, Order::Ascending) {
+        let (_, mut staker_info) = staker_info?;
+        compute_staker_reward(&config, &state, &mut staker_info)?;
+        owed_reward = owed_reward
+            .checked_add(staker_info.pending_reward)?
+            .checked_add(staker_info.referral_reward)?;
+    }
+    state.total_owed_reward = owed_reward;
+    STATE.save(storage, &state)?;
+    Ok(())
+}
+
+// register every staker as a cw4 member weighted by its shares and snapshot its voting power
+fn migrate_members(storage: &mut dyn Storage, height: u64) -> Result<(), ContractError> {
+    let config = CONFIG.load(storage)?;
+    let state = STATE.load(storage)?;
+    TOTAL.save(storage, &0)?;
+    TOTAL_WEIGHT.save(storage, &Uint128::zero())?;
+
+    let stakers = staker_info_storage()
+        .range(storage, None, None, Order::Ascending)
+        .map(|res| res.map(|item| item.1))
+        .collect::<StdResult<Vec<_>>>()?;
+    for staker_info in stakers {
+        snapshot_bond(storage, &config, height, &state, &staker_info)?;
+    }
+    Ok(())
+}
@@ -1,4 +1,6 @@
-use cosmwasm_std::StdError;
+use cosmwasm_std::{DivideByZeroError, OverflowError, StdError, Uint128};
+use cw_controllers::HookError;
+use cw_utils::ParseReplyError;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -6,6 +8,21 @@ pub enum ContractError {
     #[error("{0}")]
     Std(#[from] StdError),
 
+    #[error("{0}")]
+    Overflow(#[from] OverflowError),
+
+    #[error("{0}")]
+    DivideByZero(#[from] DivideByZeroError),
+
+    #[error("{0}")]
+    Hook(#[from] HookError),
+
+    #[error("{0}")]
+    ParseReply(#[from] ParseReplyError),
+
+    #[error("Invalid distribution slot; it must end after it starts ({start}, {end})")]
+    InvalidSchedule { start: u64, end: u64 },
+
     #[error("Unauthorized")]
     Unauthorized {},
 
@@ -36,6 +53,79 @@ pub enum ContractError {
     #[error("Cannot unbond more than bond amount")]
     ExceedBondAmount {},
 
+    #[error("No reward tokens were sent")]
+    NoRewardFunds {},
+
+    #[error("There are no vested rewards to claim")]
+    NothingToClaim {},
+
+    #[error("There is no dust to sweep")]
+    NoDust {},
+
+    #[error("There is no surplus to sweep")]
+    NothingToSweep {},
+
+    #[error("Bond must leave a position of at least {min_bond_amount}")]
+    BelowMinimumBond { min_bond_amount: Uint128 },
+
+    #[error("Bond exceeds the total staking cap; {remaining} can still be bonded")]
+    TotalBondCapExceeded { remaining: Uint128 },
+
+    #[error("Bond exceeds the per-address staking cap; {remaining} can still be bonded")]
+    AddressBondCapExceeded { remaining: Uint128 },
+
+    #[error("Loyalty tiers must be ascending by duration with multipliers of at least 1")]
+    InvalidLoyaltyMultipliers {},
+
+    #[error("Unbond by sending the receipt tokens with an unbond hook")]
+    ReceiptsRequired {},
+
+    #[error("A staker cannot refer itself")]
+    InvalidReferrer {},
+
+    #[error("NFT boosts must be greater than zero")]
+    InvalidNftBoosts {},
+
+    #[error("NFTs of this collection do not boost rewards")]
+    NftCollectionNotAllowed {},
+
+    #[error("An NFT of this collection is already locked")]
+    NftAlreadyLocked {},
+
+    #[error("No NFT of this collection is locked")]
+    NftNotLocked {},
+
+    #[error("Weight divisor must be greater than zero")]
+    InvalidWeightDivisor {},
+
+    #[error("Address is not allowed to bond")]
+    NotAllowlisted {},
+
+    #[error("Fee cannot exceed {max_bps} basis points")]
+    FeeTooHigh { max_bps: u64 },
+
+    #[error("Treasury address is not set")]
+    TreasuryNotSet {},
+
+    #[error("Compounding is only available in share mode")]
+    ShareModeDisabled {},
+
+    #[error("There are no shares to compound into")]
+    NothingToCompound {},
+
+    #[error("Unknown reply id {id}")]
+    UnknownReplyId { id: u64 },
+
     #[error("Cannot migrate from different contract type: {previous_contract}")]
     CannotMigrate { previous_contract: String },
 }
+
+// lets queries reuse the reward accounting shared with execute handlers
+impl From<ContractError> for StdError {
+    fn from(err: ContractError) -> Self {
+        match err {
+            ContractError::Std(err) => err,
+            err => StdError::generic_err(err.to_string()),
+        }
+    }
+}
@@ -1,10 +1,14 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use cosmwasm_std::{Decimal, Uint128};
+use cosmwasm_std::{to_binary, Binary, CosmosMsg, Decimal256, StdResult, Uint128, WasmMsg};
+use cw2::ContractVersion;
 use cw20::Cw20ReceiveMsg;
+use cw721::Cw721ReceiveMsg;
 
-use crate::state::{Denom, StakerInfo, UnbondingInfo};
+use crate::state::{
+    AllowlistMode, Denom, EmissionMode, IdleRewardPolicy, StakerInfo, UnbondingInfo,
+};
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct InstantiateMsg {
@@ -12,17 +16,60 @@ pub struct InstantiateMsg {
     pub reward_token: Denom,
     pub distribution_schedule: Vec<(u64, u64, Uint128)>,
     pub lock_duration: u64,
+    pub emission_mode: Option<EmissionMode>,
+    pub treasury: Option<String>,
+    pub idle_reward_policy: Option<IdleRewardPolicy>,
+    pub reward_fee_bps: Option<u64>,
+    pub deposit_fee_bps: Option<u64>,
+    pub min_bond_amount: Option<Uint128>,
+    pub max_total_bond: Option<Uint128>,
+    pub max_bond_per_address: Option<Uint128>,
+    pub allowlist_mode: Option<AllowlistMode>,
+    /// Bonded units per unit of cw4 member weight, fixed at instantiation
+    pub weight_divisor: Option<Uint128>,
+    /// DAO DAO core contract this contract serves as voting module
+    pub dao: Option<String>,
+    pub count_unbonding_power: Option<bool>,
+    pub receipt_token: Option<ReceiptTokenInfo>,
+    /// Allows compounding LP into the pool, fixed at instantiation
+    pub share_mode: Option<bool>,
+    pub reward_vesting_duration: Option<u64>,
+    pub loyalty_multipliers: Option<Vec<(u64, Decimal256)>>,
+    pub nft_boosts: Option<Vec<(String, Decimal256)>>,
+    pub referral_fee_bps: Option<u64>,
+}
+
+/// CW20 receipt token instantiated with this contract as its minter
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ReceiptTokenInfo {
+    /// Code of `hopers-receipt-token` (contracts/receipt-token), which reports every
+    /// transfer with `TransferReceipt`; a stock cw20-base would move receipts without stake
+    pub code_id: u64,
+    pub name: String,
+    pub symbol: String,
+    pub decimals: u8,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum ExecuteMsg {
     Receive(Cw20ReceiveMsg),
+    /// Lock an NFT of a boosted collection to boost the sender's rewards
+    ReceiveNft(Cw721ReceiveMsg),
+    /// Return the NFT locked from `collection`, settling rewards first
+    UnlockNft {
+        collection: String,
+    },
+    /// Unbond `amount` LP; with a receipt token, send the receipts with an unbond hook instead
     Unbond {
         amount: Uint128,
     },
-    /// Withdraw pending rewards
+    /// Withdraw pending rewards, into vesting if a vesting duration is set
     Withdraw {},
+    /// Claim the withdrawn rewards vested so far
+    ClaimVested {},
+    /// Claim the rewards diverted from the sender's referees
+    ClaimReferralRewards {},
     Redeem {},
     /// Owner operation to stop distribution on current staking contract
     /// and send remaining tokens to the new contract
@@ -42,12 +89,140 @@ pub enum ExecuteMsg {
     UpdateLockDuration {
         lock_duration: u64,
     },
+    /// Settle rewards up to now and switch emission mode or reward rate
+    UpdateEmissionMode {
+        emission_mode: EmissionMode,
+    },
+    /// Top up the reward balance emitted in continuous mode (native reward token)
+    FundRewards {},
+    /// Owner operation to send the undistributed rounding dust to `recipient`
+    SweepDust {
+        recipient: String,
+    },
+    /// Owner operation to send the balance of `denom` not owed to stakers to `recipient`
+    Sweep {
+        denom: Denom,
+        recipient: String,
+    },
+    UpdateTreasury {
+        treasury: String,
+    },
+    UpdateIdleRewardPolicy {
+        idle_reward_policy: IdleRewardPolicy,
+    },
+    /// Owner operation to set the cut of reward withdrawals sent to the treasury
+    UpdateRewardFee {
+        reward_fee_bps: u64,
+    },
+    /// Owner operation to set the cut of bonds sent to the treasury
+    UpdateDepositFee {
+        deposit_fee_bps: u64,
+    },
+    UpdateMinBondAmount {
+        min_bond_amount: Uint128,
+    },
+    /// Owner operation to set the staking caps, `None` removes a cap
+    UpdateBondCaps {
+        max_total_bond: Option<Uint128>,
+        max_bond_per_address: Option<Uint128>,
+    },
+    UpdateAllowlistMode {
+        allowlist_mode: AllowlistMode,
+    },
+    /// Owner operation to allow `addresses` to bond in internal allowlist mode
+    AddToAllowlist {
+        addresses: Vec<String>,
+    },
+    RemoveFromAllowlist {
+        addresses: Vec<String>,
+    },
+    /// Owner operation to notify `addr` of every bond and unbond
+    AddHook {
+        addr: String,
+    },
+    RemoveHook {
+        addr: String,
+    },
+    /// Owner operation to set the DAO and whether unbonding LP counts as voting power
+    UpdateVotingConfig {
+        dao: Option<String>,
+        count_unbonding_power: bool,
+    },
+    /// Owner operation to set the vesting of future withdrawals, zero pays instantly
+    UpdateRewardVestingDuration {
+        reward_vesting_duration: u64,
+    },
+    /// Owner operation to set the loyalty tiers, applied to a staker on its next update
+    UpdateLoyaltyMultipliers {
+        loyalty_multipliers: Vec<(u64, Decimal256)>,
+    },
+    /// Apply the current loyalty multiplier of `staker` to its reward weight
+    RefreshLoyalty {
+        staker: String,
+    },
+    /// Owner operation to set the boosted collections, locked NFTs keep their boost
+    UpdateNftBoosts {
+        nft_boosts: Vec<(String, Decimal256)>,
+    },
+    /// Owner operation to set the cut of referee rewards sent to referrers
+    UpdateReferralFee {
+        referral_fee_bps: u64,
+    },
+    /// Sent by `hopers-receipt-token` on every transfer between holders, not on mint or burn,
+    /// to move `amount` shares and their reward entitlement from `from` to `to`
+    TransferReceipt {
+        from: String,
+        to: String,
+        amount: Uint128,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum Cw20HookMsg {
-    Bond {},
+    /// `referrer` is recorded if this bond opens the position and it has no referrer yet
+    Bond { referrer: Option<String> },
+    /// Top up the reward balance emitted in continuous mode (cw20 reward token)
+    FundRewards {},
+    /// Owner operation to add LP to the pool without minting shares (share mode)
+    Compound {},
+    /// Unbond the LP backing the receipt tokens sent, which are burned
+    Unbond {},
+}
+
+/// StakeChangedHookMsg is sent to every registered hook under a `StakeChangeHook()`
+/// variant of its ExecuteMsg whenever a bond changes
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum StakeChangedHookMsg {
+    Stake { addr: String, amount: Uint128 },
+    Unstake { addr: String, amount: Uint128 },
+}
+
+impl StakeChangedHookMsg {
+    /// serializes the message
+    pub fn into_binary(self) -> StdResult<Binary> {
+        let msg = StakeChangedExecuteMsg::StakeChangeHook(self);
+        to_binary(&msg)
+    }
+
+    /// creates a cosmos_msg sending this struct to the named contract
+    pub fn into_cosmos_msg<T: Into<String>>(self, contract_addr: T) -> StdResult<CosmosMsg> {
+        let msg = self.into_binary()?;
+        let execute = WasmMsg::Execute {
+            contract_addr: contract_addr.into(),
+            msg,
+            funds: vec![],
+        };
+        Ok(execute.into())
+    }
+}
+
+// This is just a helper to properly serialize the above message
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+enum StakeChangedExecuteMsg {
+    StakeChangeHook(StakeChangedHookMsg),
 }
 
 /// migrate struct for distribution schedule
@@ -74,6 +249,97 @@ pub enum QueryMsg {
         start_after: Option<u64>,
         limit: Option<u32>,
     },
+    Dust {},
+    /// Current emission derived from the live schedule, defaults to the block time
+    RewardRate {
+        at_time: Option<u64>,
+    },
+    /// Project the rewards earned by bonding `amount` more for `duration` seconds,
+    /// on top of the existing bond of `address` if given
+    SimulateRewards {
+        amount: Uint128,
+        duration: u64,
+        address: Option<String>,
+    },
+    /// Dry-run a bond of `amount` by `staker` at the current block time
+    SimulateBond {
+        staker: String,
+        amount: Uint128,
+    },
+    /// Dry-run an unbond of `amount` by `staker` at the current block time
+    SimulateUnbond {
+        staker: String,
+        amount: Uint128,
+    },
+    /// Dry-run a reward withdrawal by `staker` at the current block time
+    SimulateWithdraw {
+        staker: String,
+    },
+    /// Unbonding entries of `staker` split into redeemable and still locked
+    Claims {
+        staker: String,
+    },
+    /// Unbonding entries of all stakers ordered by release time,
+    /// `start_after` is the `(time, address)` of the last entry of the previous page
+    AllUnbonding {
+        start_after: Option<(u64, String)>,
+        limit: Option<u32>,
+    },
+    /// Compare the LP and reward tokens held with what the accounting owes
+    Solvency {},
+    /// Room left under the staking caps
+    Capacity {},
+    /// Addresses of the internal allowlist
+    Allowlist {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// Contracts notified of stake changes, returns HooksResponse
+    Hooks {},
+    /// cw4 member weight derived from the bond, returns cw4::MemberResponse
+    Member {
+        addr: String,
+        at_height: Option<u64>,
+    },
+    /// Returns cw4::MemberListResponse
+    ListMembers {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// Returns cw4::TotalWeightResponse
+    TotalWeight {},
+    /// DAO DAO voting module query, `height` defaults to the current block;
+    /// power is counted in shares, unbonding LP in the shares its unbond burned
+    VotingPowerAtHeight {
+        address: String,
+        height: Option<u64>,
+    },
+    TotalPowerAtHeight {
+        height: Option<u64>,
+    },
+    /// DAO this contract serves voting power to, returns Addr
+    Dao {},
+    Info {},
+    /// LP backing a single share, returns ShareValueResponse
+    ShareValue {},
+    /// Returns LockedNftsResponse
+    LockedNfts {
+        staker: String,
+    },
+    /// Stakers whose open position was bonded with `referrer`
+    Referrals {
+        referrer: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// Referral rewards credited to `referrer`, a referee's cut is credited whenever their rewards are settled
+    ReferralEarnings {
+        referrer: String,
+    },
+    /// Withdrawn rewards of `address` still vesting or ready to claim
+    Vesting {
+        address: String,
+    },
 }
 
 // We define a custom struct for each query response
@@ -84,6 +350,24 @@ pub struct ConfigResponse {
     pub distribution_schedule: Vec<(u64, u64, Uint128)>,
     pub admin: String,
     pub lock_duration: u64,
+    pub emission_mode: EmissionMode,
+    pub treasury: Option<String>,
+    pub idle_reward_policy: IdleRewardPolicy,
+    pub reward_fee_bps: u64,
+    pub deposit_fee_bps: u64,
+    pub min_bond_amount: Uint128,
+    pub max_total_bond: Option<Uint128>,
+    pub max_bond_per_address: Option<Uint128>,
+    pub allowlist_mode: AllowlistMode,
+    pub weight_divisor: Option<Uint128>,
+    pub dao: Option<String>,
+    pub count_unbonding_power: bool,
+    pub receipt_token: Option<String>,
+    pub share_mode: bool,
+    pub reward_vesting_duration: u64,
+    pub loyalty_multipliers: Vec<(u64, Decimal256)>,
+    pub nft_boosts: Vec<(String, Decimal256)>,
+    pub referral_fee_bps: u64,
 }
 
 // We define a custom struct for each query response
@@ -91,16 +375,36 @@ pub struct ConfigResponse {
 pub struct StateResponse {
     pub last_distributed: u64,
     pub total_bond_amount: Uint128,
-    pub global_reward_index: Decimal,
+    pub global_reward_index: Decimal256,
+    pub reward_balance: Uint128,
+    pub reward_dust: Uint128,
+    pub stranded_reward: Uint128,
+    pub total_unbonding_amount: Uint128,
+    pub total_shares: Uint128,
+    pub total_vesting_amount: Uint128,
+    pub total_reward_weight: Uint128,
+    pub total_referral_reward: Uint128,
+    pub total_owed_reward: Uint128,
 }
 
 // We define a custom struct for each query response
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct StakerInfoResponse {
     pub staker: String,
-    pub reward_index: Decimal,
+    pub reward_index: Decimal256,
     pub bond_amount: Uint128,
+    pub shares: Uint128,
+    /// LP the shares can be unbonded for
+    pub bond_value: Uint128,
+    pub staked_since: u64,
+    /// loyalty multiplier earned so far, applied to `reward_weight` on refresh
+    pub loyalty_multiplier: Decimal256,
+    pub nft_boost: Decimal256,
+    pub reward_weight: Uint128,
+    pub referrer: Option<String>,
     pub pending_reward: Uint128,
+    /// pending reward net of the reward fee
+    pub claimable_reward: Uint128,
     pub total_earned: Uint128,
 }
 
@@ -114,3 +418,179 @@ pub struct UnbondingInfoResponse {
     pub unbonding_info: Vec<UnbondingInfo>,
     pub crr_time: u64,
 }
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct DustResponse {
+    pub dust: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RewardRateResponse {
+    pub at_time: u64,
+    pub total_bond_amount: Uint128,
+    /// bonds weighted by loyalty and NFT boosts, which the emission is spread over
+    pub total_reward_weight: Uint128,
+    /// rewards emitted per second across all active slots
+    pub reward_per_second: Decimal256,
+    /// rewards earned per second by 10^18 units of reward weight, like `global_reward_index`;
+    /// a staker earns it times its `reward_weight`
+    pub reward_per_unit_per_second: Decimal256,
+    /// rewards earned per year by 10^18 units of reward weight at the current rate
+    pub annualized_reward_per_unit: Decimal256,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SimulateRewardsResponse {
+    pub end_time: u64,
+    /// bond of the staker including the simulated amount
+    pub bond_amount: Uint128,
+    /// total bond including the simulated amount
+    pub total_bond_amount: Uint128,
+    /// rewards earned over the simulated duration
+    pub projected_reward: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SimulationResponse {
+    pub staker: String,
+    /// bond of the staker after the operation
+    pub bond_amount: Uint128,
+    /// reward left pending after the operation
+    pub pending_reward: Uint128,
+    /// reward paid out by the operation, net of the reward fee
+    pub withdrawn_reward: Uint128,
+    /// reward moved into vesting by the operation instead of being paid out, net of the reward fee
+    pub vesting_reward: Uint128,
+    pub total_bond_amount: Uint128,
+    /// error the operation would fail with, in which case the position is left unchanged
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Claim {
+    pub amount: Uint128,
+    /// time the amount was unbonded
+    pub time: u64,
+    /// time the amount becomes redeemable
+    pub release_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ClaimsResponse {
+    /// total that `Redeem` would transfer now
+    pub redeemable_amount: Uint128,
+    pub locked_amount: Uint128,
+    /// earliest release time among the locked entries
+    pub next_release_at: Option<u64>,
+    pub claims: Vec<Claim>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct UnbondingEntry {
+    pub address: String,
+    pub amount: Uint128,
+    pub time: u64,
+    pub release_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AllUnbondingResponse {
+    pub entries: Vec<UnbondingEntry>,
+    pub total_unbonding_amount: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AllowlistResponse {
+    pub addresses: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct CapacityResponse {
+    pub total_bond_amount: Uint128,
+    pub max_total_bond: Option<Uint128>,
+    pub max_bond_per_address: Option<Uint128>,
+    /// amount that can still be bonded in total, `None` if uncapped
+    pub remaining_total_bond: Option<Uint128>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SolvencyResponse {
+    /// LP held by the contract
+    pub lp_token_balance: Uint128,
+    /// bonded plus unbonding LP
+    pub lp_required: Uint128,
+    pub lp_surplus: Uint128,
+    pub lp_deficit: Uint128,
+    /// reward tokens held by the contract
+    pub reward_token_balance: Uint128,
+    /// pending rewards of all stakers, rounded up
+    pub owed_reward: Uint128,
+    /// withdrawn rewards vesting or vested but not claimed yet
+    pub vesting_reward: Uint128,
+    /// referral rewards credited to referrers but not claimed yet
+    pub referral_reward: Uint128,
+    /// rewards not emitted yet, scheduled or funded
+    pub remaining_reward: Uint128,
+    /// owed, vesting, referral and remaining rewards plus dust and stranded rewards
+    pub reward_required: Uint128,
+    pub reward_surplus: Uint128,
+    pub reward_deficit: Uint128,
+    pub solvent: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct VotingPowerAtHeightResponse {
+    pub power: Uint128,
+    pub height: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TotalPowerAtHeightResponse {
+    pub power: Uint128,
+    pub height: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct InfoResponse {
+    pub info: ContractVersion,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ShareValueResponse {
+    pub total_shares: Uint128,
+    pub total_bond_amount: Uint128,
+    /// LP per share
+    pub share_value: Decimal256,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct VestingResponse {
+    pub locked_amount: Uint128,
+    /// vested rewards `ClaimVested` would pay now
+    pub claimable_amount: Uint128,
+    pub end_time: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LockedNftEntry {
+    pub collection: String,
+    pub token_id: String,
+    pub boost: Decimal256,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LockedNftsResponse {
+    pub nfts: Vec<LockedNftEntry>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ReferralsResponse {
+    pub referees: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ReferralEarningsResponse {
+    /// rewards claimable with ClaimReferralRewards
+    pub pending: Uint128,
+    pub total: Uint128,
+}
@@ -1,19 +1,39 @@
-use crate::contract::{compute_reward, compute_staker_reward};
+use crate::contract::{
+    apply_bond, apply_unbond, apply_withdraw, assert_allowlisted, compute_emission_rate,
+    compute_reward, compute_scheduled_amount, compute_staker_reward, increase_bond_amount,
+    loyalty_multiplier, reward_fee, settle_vesting, shares_to_amount, update_loyalty,
+    REWARD_INDEX_SCALE,
+};
+use crate::error::ContractError;
 use crate::msg::{
-    ConfigResponse, QueryMsg, StakerInfoResponse, StakersListResponse, StateResponse,
-    UnbondingInfoResponse,
+    AllUnbondingResponse, AllowlistResponse, CapacityResponse, Claim, ClaimsResponse,
+    ConfigResponse, DustResponse, InfoResponse, LockedNftEntry, LockedNftsResponse, QueryMsg,
+    ReferralEarningsResponse, ReferralsResponse, RewardRateResponse, ShareValueResponse,
+    SimulateRewardsResponse, SimulationResponse, SolvencyResponse, StakerInfoResponse,
+    StakersListResponse, StateResponse, TotalPowerAtHeightResponse, UnbondingEntry,
+    UnbondingInfoResponse, VestingResponse, VotingPowerAtHeightResponse,
 };
 use crate::state::{
     staker_info_key, staker_info_storage, unbonding_info_storage, user_earned_info_key,
-    user_earned_info_storage, UnbondingInfo, CONFIG, STATE,
+    user_earned_info_storage, Config, Denom, EmissionMode, StakerInfo, State, UnbondingInfo,
+    ALLOWLIST, BONDED_BALANCES, BONDED_TOTAL, CONFIG, HOOKS, LOCKED_NFTS, MEMBERS, REFERRALS,
+    REFERRAL_EARNINGS, STATE, TOTAL, UNBONDING_BALANCES, UNBONDING_TOTAL, VESTING,
+};
+use cosmwasm_std::{
+    entry_point, to_binary, Addr, Binary, Decimal256, Deps, Env, Order, QuerierWrapper, StdError,
+    StdResult, Uint128, Uint256,
 };
-use cosmwasm_std::{entry_point, to_binary, Binary, Decimal, Deps, Env, Order, StdResult, Uint128};
+use cw2::get_contract_version;
+use cw20::{BalanceResponse, Cw20QueryMsg};
+use cw4::{Member, MemberListResponse, MemberResponse, TotalWeightResponse};
 use cw_storage_plus::Bound;
 
 // Query limits
 const DEFAULT_QUERY_LIMIT: u32 = 10;
 const MAX_QUERY_LIMIT: u32 = 30;
 
+const SECONDS_PER_YEAR: u64 = 365 * 24 * 60 * 60;
+
 #[entry_point]
 pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
     match msg {
@@ -34,6 +54,60 @@ pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
             start_after,
             limit,
         )?),
+        QueryMsg::Dust {} => to_binary(&query_dust(deps, env)?),
+        QueryMsg::RewardRate { at_time } => to_binary(&query_reward_rate(deps, env, at_time)?),
+        QueryMsg::SimulateRewards {
+            amount,
+            duration,
+            address,
+        } => to_binary(&query_simulate_rewards(
+            deps, env, amount, duration, address,
+        )?),
+        QueryMsg::SimulateBond { staker, amount } => {
+            to_binary(&query_simulate_bond(deps, env, staker, amount)?)
+        }
+        QueryMsg::SimulateUnbond { staker, amount } => {
+            to_binary(&query_simulate_unbond(deps, env, staker, amount)?)
+        }
+        QueryMsg::SimulateWithdraw { staker } => {
+            to_binary(&query_simulate_withdraw(deps, env, staker)?)
+        }
+        QueryMsg::Claims { staker } => to_binary(&query_claims(deps, env, staker)?),
+        QueryMsg::AllUnbonding { start_after, limit } => {
+            to_binary(&query_all_unbonding(deps, start_after, limit)?)
+        }
+        QueryMsg::Solvency {} => to_binary(&query_solvency(deps, env)?),
+        QueryMsg::Capacity {} => to_binary(&query_capacity(deps)?),
+        QueryMsg::Allowlist { start_after, limit } => {
+            to_binary(&query_allowlist(deps, start_after, limit)?)
+        }
+        QueryMsg::Hooks {} => to_binary(&HOOKS.query_hooks(deps)?),
+        QueryMsg::Member { addr, at_height } => to_binary(&query_member(deps, addr, at_height)?),
+        QueryMsg::ListMembers { start_after, limit } => {
+            to_binary(&query_list_members(deps, start_after, limit)?)
+        }
+        QueryMsg::TotalWeight {} => to_binary(&query_total_weight(deps)?),
+        QueryMsg::VotingPowerAtHeight { address, height } => {
+            to_binary(&query_voting_power_at_height(deps, env, address, height)?)
+        }
+        QueryMsg::TotalPowerAtHeight { height } => {
+            to_binary(&query_total_power_at_height(deps, env, height)?)
+        }
+        QueryMsg::Dao {} => to_binary(&query_dao(deps)?),
+        QueryMsg::ShareValue {} => to_binary(&query_share_value(deps)?),
+        QueryMsg::LockedNfts { staker } => to_binary(&query_locked_nfts(deps, staker)?),
+        QueryMsg::Referrals {
+            referrer,
+            start_after,
+            limit,
+        } => to_binary(&query_referrals(deps, referrer, start_after, limit)?),
+        QueryMsg::ReferralEarnings { referrer } => {
+            to_binary(&query_referral_earnings(deps, referrer)?)
+        }
+        QueryMsg::Vesting { address } => to_binary(&query_vesting(deps, env, address)?),
+        QueryMsg::Info {} => to_binary(&InfoResponse {
+            info: get_contract_version(deps.storage)?,
+        }),
     }
 }
 
@@ -45,6 +119,24 @@ pub fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
         distribution_schedule: config.distribution_schedule,
         admin: config.admin,
         lock_duration: config.lock_duration,
+        emission_mode: config.emission_mode,
+        treasury: config.treasury,
+        idle_reward_policy: config.idle_reward_policy,
+        reward_fee_bps: config.reward_fee_bps,
+        deposit_fee_bps: config.deposit_fee_bps,
+        min_bond_amount: config.min_bond_amount,
+        max_total_bond: config.max_total_bond,
+        max_bond_per_address: config.max_bond_per_address,
+        allowlist_mode: config.allowlist_mode,
+        weight_divisor: config.weight_divisor,
+        dao: config.dao,
+        count_unbonding_power: config.count_unbonding_power,
+        receipt_token: config.receipt_token,
+        share_mode: config.share_mode,
+        reward_vesting_duration: config.reward_vesting_duration,
+        loyalty_multipliers: config.loyalty_multipliers,
+        nft_boosts: config.nft_boosts,
+        referral_fee_bps: config.referral_fee_bps,
     })
 }
 
@@ -52,13 +144,22 @@ pub fn query_state(deps: Deps, block_time: Option<u64>) -> StdResult<StateRespon
     let mut state = STATE.load(deps.storage)?;
     if let Some(block_time) = block_time {
         let config = CONFIG.load(deps.storage)?;
-        compute_reward(&config, &mut state, block_time);
+        compute_reward(&config, &mut state, block_time)?;
     }
 
     Ok(StateResponse {
         last_distributed: state.last_distributed,
         total_bond_amount: state.total_bond_amount,
         global_reward_index: state.global_reward_index,
+        reward_balance: state.reward_balance,
+        reward_dust: state.reward_dust,
+        stranded_reward: state.stranded_reward,
+        total_unbonding_amount: state.total_unbonding_amount,
+        total_shares: state.total_shares,
+        total_vesting_amount: state.total_vesting_amount,
+        total_reward_weight: state.total_reward_weight,
+        total_referral_reward: state.total_referral_reward,
+        total_owed_reward: state.total_owed_reward,
     })
 }
 
@@ -68,12 +169,11 @@ pub fn query_staker_info(deps: Deps, env: Env, staker: String) -> StdResult<Stak
     match staker_info_storage().may_load(deps.storage, staker_info_key)? {
         Some(some_staker_info) => {
             let mut staker_info = some_staker_info;
+            let config = CONFIG.load(deps.storage)?;
+            let mut state = STATE.load(deps.storage)?;
             if let Some(block_time) = block_time {
-                let config = CONFIG.load(deps.storage)?;
-                let mut state = STATE.load(deps.storage)?;
-
-                compute_reward(&config, &mut state, block_time);
-                compute_staker_reward(&state, &mut staker_info)?;
+                compute_reward(&config, &mut state, block_time)?;
+                compute_staker_reward(&config, &state, &mut staker_info)?;
             }
 
             let total_earned: Uint128;
@@ -92,15 +192,37 @@ pub fn query_staker_info(deps: Deps, env: Env, staker: String) -> StdResult<Stak
                 staker,
                 reward_index: staker_info.reward_index,
                 bond_amount: staker_info.bond_amount,
+                shares: staker_info.shares,
+                bond_value: shares_to_amount(&state, staker_info.shares)?,
+                staked_since: staker_info.staked_since,
+                loyalty_multiplier: loyalty_multiplier(
+                    &config,
+                    &staker_info,
+                    env.block.time.seconds(),
+                ),
+                nft_boost: staker_info.nft_boost,
+                reward_weight: staker_info.reward_weight,
+                referrer: staker_info.referrer,
                 pending_reward: staker_info.pending_reward,
+                claimable_reward: staker_info
+                    .pending_reward
+                    .checked_sub(reward_fee(&config, staker_info.pending_reward))?,
                 total_earned,
             })
         }
         None => Ok(StakerInfoResponse {
             staker,
-            reward_index: Decimal::zero(),
+            reward_index: Decimal256::zero(),
             bond_amount: Uint128::zero(),
+            shares: Uint128::zero(),
+            bond_value: Uint128::zero(),
+            staked_since: 0,
+            loyalty_multiplier: Decimal256::one(),
+            nft_boost: Decimal256::zero(),
+            reward_weight: Uint128::zero(),
+            referrer: None,
             pending_reward: Uint128::zero(),
+            claimable_reward: Uint128::zero(),
             total_earned: Uint128::zero(),
         }),
     }
@@ -168,3 +290,551 @@ pub fn query_all_unbonding_info(
 
     Ok(unbonding_info)
 }
+
+pub fn query_claims(deps: Deps, env: Env, staker: String) -> StdResult<ClaimsResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    let crr_time = env.block.time.seconds();
+
+    let mut redeemable_amount = Uint128::zero();
+    let mut locked_amount = Uint128::zero();
+    let mut next_release_at: Option<u64> = None;
+
+    let claims = query_all_unbonding_info(deps, env, staker)?
+        .into_iter()
+        .map(|unbonding_info| {
+            let release_at = unbonding_info.time.saturating_add(config.lock_duration);
+            if release_at > crr_time {
+                locked_amount = locked_amount.checked_add(unbonding_info.amount)?;
+                next_release_at = Some(next_release_at.map_or(release_at, |t| t.min(release_at)));
+            } else {
+                redeemable_amount = redeemable_amount.checked_add(unbonding_info.amount)?;
+            }
+            Ok(Claim {
+                amount: unbonding_info.amount,
+                time: unbonding_info.time,
+                release_at,
+            })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(ClaimsResponse {
+        redeemable_amount,
+        locked_amount,
+        next_release_at,
+        claims,
+    })
+}
+
+pub fn query_all_unbonding(
+    deps: Deps,
+    start_after: Option<(u64, String)>,
+    limit: Option<u32>,
+) -> StdResult<AllUnbondingResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    let state = STATE.load(deps.storage)?;
+    let limit = limit.unwrap_or(DEFAULT_QUERY_LIMIT).min(MAX_QUERY_LIMIT) as usize;
+    // every entry shares the lock duration, so unbond time orders release time too
+    let start = start_after.map(|(time, address)| Bound::exclusive((time, (address, time))));
+
+    let entries = unbonding_info_storage()
+        .idx
+        .time
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|res| {
+            res.map(|(_, unbonding_info)| UnbondingEntry {
+                release_at: unbonding_info.time.saturating_add(config.lock_duration),
+                address: unbonding_info.address,
+                amount: unbonding_info.amount,
+                time: unbonding_info.time,
+            })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(AllUnbondingResponse {
+        entries,
+        total_unbonding_amount: state.total_unbonding_amount,
+    })
+}
+
+pub fn query_allowlist(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<AllowlistResponse> {
+    let limit = limit.unwrap_or(DEFAULT_QUERY_LIMIT).min(MAX_QUERY_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let addresses = ALLOWLIST
+        .keys(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .collect::<StdResult<Vec<_>>>()?;
+    Ok(AllowlistResponse { addresses })
+}
+
+pub fn query_member(deps: Deps, addr: String, at_height: Option<u64>) -> StdResult<MemberResponse> {
+    let addr = deps.api.addr_validate(&addr)?;
+    let weight = match at_height {
+        Some(height) => MEMBERS.may_load_at_height(deps.storage, &addr, height)?,
+        None => MEMBERS.may_load(deps.storage, &addr)?,
+    };
+    Ok(MemberResponse { weight })
+}
+
+pub fn query_list_members(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<MemberListResponse> {
+    let limit = limit.unwrap_or(DEFAULT_QUERY_LIMIT).min(MAX_QUERY_LIMIT) as usize;
+    let start_after = start_after
+        .map(|addr| deps.api.addr_validate(&addr))
+        .transpose()?;
+    let start = start_after.as_ref().map(Bound::exclusive);
+
+    let members = MEMBERS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            item.map(|(addr, weight)| Member {
+                addr: addr.into(),
+                weight,
+            })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+    Ok(MemberListResponse { members })
+}
+
+pub fn query_total_weight(deps: Deps) -> StdResult<TotalWeightResponse> {
+    let weight = TOTAL.may_load(deps.storage)?.unwrap_or_default();
+    Ok(TotalWeightResponse { weight })
+}
+
+// bonded shares, plus the shares burned by pending unbonds if the config counts them, as of `height`
+pub fn query_voting_power_at_height(
+    deps: Deps,
+    env: Env,
+    address: String,
+    height: Option<u64>,
+) -> StdResult<VotingPowerAtHeightResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    let address = deps.api.addr_validate(&address)?;
+    let height = height.unwrap_or(env.block.height);
+
+    let mut power = BONDED_BALANCES
+        .may_load_at_height(deps.storage, &address, height)?
+        .unwrap_or_default();
+    if config.count_unbonding_power {
+        power = power.checked_add(
+            UNBONDING_BALANCES
+                .may_load_at_height(deps.storage, &address, height)?
+                .unwrap_or_default(),
+        )?;
+    }
+
+    Ok(VotingPowerAtHeightResponse { power, height })
+}
+
+pub fn query_total_power_at_height(
+    deps: Deps,
+    env: Env,
+    height: Option<u64>,
+) -> StdResult<TotalPowerAtHeightResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    let height = height.unwrap_or(env.block.height);
+
+    let mut power = BONDED_TOTAL
+        .may_load_at_height(deps.storage, height)?
+        .unwrap_or_default();
+    if config.count_unbonding_power {
+        power = power.checked_add(
+            UNBONDING_TOTAL
+                .may_load_at_height(deps.storage, height)?
+                .unwrap_or_default(),
+        )?;
+    }
+
+    Ok(TotalPowerAtHeightResponse { power, height })
+}
+
+pub fn query_share_value(deps: Deps) -> StdResult<ShareValueResponse> {
+    let state = STATE.load(deps.storage)?;
+    let share_value = if state.total_shares.is_zero() {
+        Decimal256::one()
+    } else {
+        Decimal256::from_ratio(state.total_bond_amount, state.total_shares)
+    };
+
+    Ok(ShareValueResponse {
+        total_shares: state.total_shares,
+        total_bond_amount: state.total_bond_amount,
+        share_value,
+    })
+}
+
+pub fn query_locked_nfts(deps: Deps, staker: String) -> StdResult<LockedNftsResponse> {
+    let staker = deps.api.addr_validate(&staker)?;
+    let nfts = LOCKED_NFTS
+        .prefix(&staker)
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| {
+            let (collection, locked_nft) = item?;
+            Ok(LockedNftEntry {
+                collection: collection.to_string(),
+                token_id: locked_nft.token_id,
+                boost: locked_nft.boost,
+            })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(LockedNftsResponse { nfts })
+}
+
+pub fn query_referrals(
+    deps: Deps,
+    referrer: String,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<ReferralsResponse> {
+    let referrer = deps.api.addr_validate(&referrer)?;
+    let limit = limit.unwrap_or(DEFAULT_QUERY_LIMIT).min(MAX_QUERY_LIMIT) as usize;
+    let start_after = start_after
+        .map(|addr| deps.api.addr_validate(&addr))
+        .transpose()?;
+    let start = start_after.as_ref().map(Bound::exclusive);
+
+    let referees = REFERRALS
+        .prefix(&referrer)
+        .keys(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|referee| referee.map(String::from))
+        .collect::<StdResult<Vec<_>>>()?;
+    Ok(ReferralsResponse { referees })
+}
+
+// earnings credited so far; a referee's cut is only credited when its rewards are settled
+pub fn query_referral_earnings(
+    deps: Deps,
+    referrer: String,
+) -> StdResult<ReferralEarningsResponse> {
+    let referrer = deps.api.addr_validate(&referrer)?;
+    let earnings = REFERRAL_EARNINGS
+        .may_load(deps.storage, &referrer)?
+        .unwrap_or_default();
+
+    Ok(ReferralEarningsResponse {
+        pending: earnings.pending,
+        total: earnings.total,
+    })
+}
+
+pub fn query_vesting(deps: Deps, env: Env, address: String) -> StdResult<VestingResponse> {
+    let address = deps.api.addr_validate(&address)?;
+    let mut vesting = VESTING
+        .may_load(deps.storage, &address)?
+        .unwrap_or_default();
+    settle_vesting(&mut vesting, env.block.time.seconds())?;
+
+    Ok(VestingResponse {
+        locked_amount: vesting.locked_amount,
+        claimable_amount: vesting.vested_amount,
+        end_time: vesting.end_time,
+    })
+}
+
+pub fn query_dao(deps: Deps) -> StdResult<Addr> {
+    let config = CONFIG.load(deps.storage)?;
+    config
+        .dao
+        .map(Addr::unchecked)
+        .ok_or_else(|| StdError::not_found("dao"))
+}
+
+pub fn query_capacity(deps: Deps) -> StdResult<CapacityResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    let state = STATE.load(deps.storage)?;
+
+    Ok(CapacityResponse {
+        total_bond_amount: state.total_bond_amount,
+        max_total_bond: config.max_total_bond,
+        max_bond_per_address: config.max_bond_per_address,
+        remaining_total_bond: config
+            .max_total_bond
+            .map(|max| max.saturating_sub(state.total_bond_amount)),
+    })
+}
+
+pub fn query_solvency(deps: Deps, env: Env) -> StdResult<SolvencyResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    let mut state = STATE.load(deps.storage)?;
+    compute_reward(&config, &mut state, env.block.time.seconds())?;
+
+    let contract_addr = env.contract.address.to_string();
+    let lp_token_balance =
+        query_cw20_balance(&deps.querier, &config.lp_token_contract, &contract_addr)?;
+    let reward_token_balance =
+        query_token_balance(&deps.querier, &config.reward_token, &contract_addr)?;
+
+    let lp_required = state
+        .total_bond_amount
+        .checked_add(state.total_unbonding_amount)?;
+
+    let owed_reward = state.total_owed_reward;
+
+    // funded rewards are held for continuous mode even while the schedule is active
+    let remaining_reward = match config.emission_mode {
+        EmissionMode::Schedule => {
+            compute_scheduled_amount(&config, state.last_distributed, u64::MAX)?
+                .checked_add(state.reward_balance)?
+        }
+        EmissionMode::Continuous { .. } => state.reward_balance,
+    };
+    let reward_required = owed_reward
+        .checked_add(state.total_vesting_amount)?
+        .checked_add(state.total_referral_reward)?
+        .checked_add(remaining_reward)?
+        .checked_add(state.reward_dust)?
+        .checked_add(state.stranded_reward)?;
+
+    Ok(SolvencyResponse {
+        lp_token_balance,
+        lp_required,
+        lp_surplus: lp_token_balance.saturating_sub(lp_required),
+        lp_deficit: lp_required.saturating_sub(lp_token_balance),
+        reward_token_balance,
+        owed_reward,
+        vesting_reward: state.total_vesting_amount,
+        referral_reward: state.total_referral_reward,
+        remaining_reward,
+        reward_required,
+        reward_surplus: reward_token_balance.saturating_sub(reward_required),
+        reward_deficit: reward_required.saturating_sub(reward_token_balance),
+        solvent: lp_token_balance >= lp_required && reward_token_balance >= reward_required,
+    })
+}
+
+pub fn query_token_balance(
+    querier: &QuerierWrapper,
+    denom: &Denom,
+    address: &str,
+) -> StdResult<Uint128> {
+    match denom {
+        Denom::Native(denom) => Ok(querier.query_balance(address, denom)?.amount),
+        Denom::Cw20(token) => query_cw20_balance(querier, token.as_str(), address),
+    }
+}
+
+pub fn query_cw20_balance(
+    querier: &QuerierWrapper,
+    token: &str,
+    address: &str,
+) -> StdResult<Uint128> {
+    let res: BalanceResponse = querier.query_wasm_smart(
+        token,
+        &Cw20QueryMsg::Balance {
+            address: address.to_string(),
+        },
+    )?;
+    Ok(res.balance)
+}
+
+pub fn query_dust(deps: Deps, env: Env) -> StdResult<DustResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    let mut state = STATE.load(deps.storage)?;
+    compute_reward(&config, &mut state, env.block.time.seconds())?;
+
+    Ok(DustResponse {
+        dust: state.reward_dust,
+    })
+}
+
+pub fn query_reward_rate(
+    deps: Deps,
+    env: Env,
+    at_time: Option<u64>,
+) -> StdResult<RewardRateResponse> {
+    let at_time = at_time.unwrap_or_else(|| env.block.time.seconds());
+    let config = CONFIG.load(deps.storage)?;
+    let mut state = STATE.load(deps.storage)?;
+    compute_reward(&config, &mut state, at_time)?;
+
+    let reward_per_second = compute_emission_rate(&config, &state, at_time)?;
+
+    // spread over the reward weight at the same scale as the reward index
+    let per_unit = |seconds: u64| -> StdResult<Decimal256> {
+        if state.total_reward_weight.is_zero() {
+            return Ok(Decimal256::zero());
+        }
+        Ok(Decimal256::new(
+            reward_per_second
+                .atomics()
+                .checked_mul(Uint256::from(REWARD_INDEX_SCALE))?
+                .checked_mul(Uint256::from(seconds))?
+                .checked_div(Uint256::from(state.total_reward_weight))?,
+        ))
+    };
+
+    Ok(RewardRateResponse {
+        at_time,
+        total_bond_amount: state.total_bond_amount,
+        total_reward_weight: state.total_reward_weight,
+        reward_per_second,
+        reward_per_unit_per_second: per_unit(1)?,
+        annualized_reward_per_unit: per_unit(SECONDS_PER_YEAR)?,
+    })
+}
+
+pub fn query_simulate_rewards(
+    deps: Deps,
+    env: Env,
+    amount: Uint128,
+    duration: u64,
+    address: Option<String>,
+) -> StdResult<SimulateRewardsResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    let mut state = STATE.load(deps.storage)?;
+    let block_time = env.block.time.seconds();
+    let end_time = block_time.saturating_add(duration);
+
+    let existing_staker_info = match &address {
+        Some(address) => staker_info_storage().may_load(deps.storage, staker_info_key(address))?,
+        None => None,
+    };
+    let mut staker_info =
+        existing_staker_info.unwrap_or_else(|| StakerInfo::new(address.unwrap_or_default()));
+
+    // settle up to now, then let the diluted pool run along the schedule
+    compute_reward(&config, &mut state, block_time)?;
+    compute_staker_reward(&config, &state, &mut staker_info)?;
+    let pending_reward = staker_info.pending_reward;
+
+    let shares = staker_info.shares;
+    increase_bond_amount(&mut state, &mut staker_info, amount)?;
+    update_loyalty(&config, &mut state, &mut staker_info, shares, block_time)?;
+
+    compute_reward(&config, &mut state, end_time)?;
+    compute_staker_reward(&config, &state, &mut staker_info)?;
+
+    Ok(SimulateRewardsResponse {
+        end_time,
+        bond_amount: staker_info.bond_amount,
+        total_bond_amount: state.total_bond_amount,
+        projected_reward: staker_info.pending_reward.checked_sub(pending_reward)?,
+    })
+}
+
+pub fn query_simulate_bond(
+    deps: Deps,
+    env: Env,
+    staker: String,
+    amount: Uint128,
+) -> StdResult<SimulationResponse> {
+    let allowed = assert_allowlisted(deps, &CONFIG.load(deps.storage)?, &staker);
+    simulate(
+        deps,
+        env,
+        staker,
+        false,
+        |config, state, staker_info, time| {
+            allowed?;
+            apply_bond(config, state, staker_info, amount, time).map(|_| Uint128::zero())
+        },
+    )
+}
+
+pub fn query_simulate_unbond(
+    deps: Deps,
+    env: Env,
+    staker: String,
+    amount: Uint128,
+) -> StdResult<SimulationResponse> {
+    simulate(
+        deps,
+        env,
+        staker,
+        true,
+        |config, state, staker_info, time| {
+            apply_unbond(config, state, staker_info, amount, time).map(|_| Uint128::zero())
+        },
+    )
+}
+
+pub fn query_simulate_withdraw(
+    deps: Deps,
+    env: Env,
+    staker: String,
+) -> StdResult<SimulationResponse> {
+    simulate(
+        deps,
+        env,
+        staker,
+        true,
+        |config, state, staker_info, time| {
+            let amount = apply_withdraw(config, state, staker_info, time)?;
+            Ok(amount.checked_sub(reward_fee(config, amount))?)
+        },
+    )
+}
+
+// run `transition` on copies of the state and the staker position at the current block time,
+// reporting the error instead of failing the query; `transition` returns the withdrawn reward
+fn simulate<F>(
+    deps: Deps,
+    env: Env,
+    staker: String,
+    must_be_staked: bool,
+    transition: F,
+) -> StdResult<SimulationResponse>
+where
+    F: FnOnce(&Config, &mut State, &mut StakerInfo, u64) -> Result<Uint128, ContractError>,
+{
+    let config = CONFIG.load(deps.storage)?;
+    let state = STATE.load(deps.storage)?;
+    let existing_staker_info =
+        staker_info_storage().may_load(deps.storage, staker_info_key(&staker))?;
+    let staker_info = existing_staker_info
+        .clone()
+        .unwrap_or_else(|| StakerInfo::new(staker.clone()));
+
+    let mut new_state = state.clone();
+    let mut new_staker_info = staker_info.clone();
+    let result = if must_be_staked && existing_staker_info.is_none() {
+        Err(ContractError::NotStaked {})
+    } else {
+        transition(
+            &config,
+            &mut new_state,
+            &mut new_staker_info,
+            env.block.time.seconds(),
+        )
+    };
+
+    Ok(match result {
+        Ok(reward) => {
+            // rewards withdrawn under vesting are only paid out by ClaimVested
+            let (withdrawn_reward, vesting_reward) = if config.reward_vesting_duration == 0 {
+                (reward, Uint128::zero())
+            } else {
+                (Uint128::zero(), reward)
+            };
+            SimulationResponse {
+                staker,
+                bond_amount: new_staker_info.bond_amount,
+                pending_reward: new_staker_info.pending_reward,
+                withdrawn_reward,
+                vesting_reward,
+                total_bond_amount: new_state.total_bond_amount,
+                error: None,
+            }
+        }
+        Err(err) => SimulationResponse {
+            staker,
+            bond_amount: staker_info.bond_amount,
+            pending_reward: staker_info.pending_reward,
+            withdrawn_reward: Uint128::zero(),
+            vesting_reward: Uint128::zero(),
+            total_bond_amount: state.total_bond_amount,
+            error: Some(err.to_string()),
+        },
+    })
+}
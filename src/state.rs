@@ -1,10 +1,62 @@
-use cosmwasm_std::{Addr, Decimal, Uint128};
-use cw_storage_plus::{Index, IndexList, IndexedMap, Item, MultiIndex};
+use cosmwasm_std::{Addr, Decimal256, Empty, Uint128};
+use cw4::{MEMBERS_CHANGELOG, MEMBERS_CHECKPOINTS, MEMBERS_KEY, TOTAL_KEY};
+use cw_controllers::Hooks;
+use cw_storage_plus::{
+    Index, IndexList, IndexedMap, Item, Map, MultiIndex, SnapshotItem, SnapshotMap, Strategy,
+};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 pub const CONFIG: Item<Config> = Item::new("config_config");
 pub const STATE: Item<State> = Item::new("config_state");
+// addresses allowed to bond under `AllowlistMode::Internal`
+pub const ALLOWLIST: Map<String, bool> = Map::new("allowlist");
+// contracts notified with a `StakeChangedHookMsg` on bond and unbond
+pub const HOOKS: Hooks = Hooks::new("hooks");
+// stakers as weighted cw4 members, under the cw4 keys so raw cw4 queries work too
+pub const MEMBERS: SnapshotMap<&Addr, u64> = SnapshotMap::new(
+    MEMBERS_KEY,
+    MEMBERS_CHECKPOINTS,
+    MEMBERS_CHANGELOG,
+    Strategy::EveryBlock,
+);
+pub const TOTAL: Item<u64> = Item::new(TOTAL_KEY);
+// exact sum of member weights, `TOTAL` caps it to u64
+pub const TOTAL_WEIGHT: Item<Uint128> = Item::new("total_weight");
+// withdrawn rewards paid out linearly under `Config.reward_vesting_duration`
+pub const VESTING: Map<&Addr, VestingInfo> = Map::new("vesting");
+// NFTs a staker locked for a reward boost, one per collection
+pub const LOCKED_NFTS: Map<(&Addr, &Addr), LockedNft> = Map::new("locked_nfts");
+// `(referrer, referee)` of every open position bonded with a referrer
+pub const REFERRALS: Map<(&Addr, &Addr), Empty> = Map::new("referrals");
+// referee rewards credited to each referrer
+pub const REFERRAL_EARNINGS: Map<&Addr, ReferralEarnings> = Map::new("referral_earnings");
+// bonded shares and unbonding LP per staker and in total, snapshotted for voting power;
+// shares equal LP unless compounded, and compounding leaves them untouched
+pub const BONDED_BALANCES: SnapshotMap<&Addr, Uint128> = SnapshotMap::new(
+    "bonded_balances",
+    "bonded_balances__checkpoints",
+    "bonded_balances__changelog",
+    Strategy::EveryBlock,
+);
+pub const BONDED_TOTAL: SnapshotItem<Uint128> = SnapshotItem::new(
+    "bonded_total",
+    "bonded_total__checkpoints",
+    "bonded_total__changelog",
+    Strategy::EveryBlock,
+);
+pub const UNBONDING_BALANCES: SnapshotMap<&Addr, Uint128> = SnapshotMap::new(
+    "unbonding_balances",
+    "unbonding_balances__checkpoints",
+    "unbonding_balances__changelog",
+    Strategy::EveryBlock,
+);
+pub const UNBONDING_TOTAL: SnapshotItem<Uint128> = SnapshotItem::new(
+    "unbonding_total",
+    "unbonding_total__checkpoints",
+    "unbonding_total__changelog",
+    Strategy::EveryBlock,
+);
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
@@ -20,21 +72,180 @@ pub struct Config {
     pub distribution_schedule: Vec<(u64, u64, Uint128)>,
     pub admin: String,
     pub lock_duration: u64,
+    #[serde(default)]
+    pub emission_mode: EmissionMode,
+    #[serde(default)]
+    pub treasury: Option<String>,
+    #[serde(default)]
+    pub idle_reward_policy: IdleRewardPolicy,
+    /// cut of every reward withdrawal sent to `treasury`, in basis points
+    #[serde(default)]
+    pub reward_fee_bps: u64,
+    /// cut of every bond sent to `treasury`, in basis points
+    #[serde(default)]
+    pub deposit_fee_bps: u64,
+    /// smallest position a bond may leave behind
+    #[serde(default)]
+    pub min_bond_amount: Uint128,
+    /// cap on `State.total_bond_amount`, uncapped if unset
+    #[serde(default)]
+    pub max_total_bond: Option<Uint128>,
+    /// cap on the bond of a single staker, uncapped if unset
+    #[serde(default)]
+    pub max_bond_per_address: Option<Uint128>,
+    #[serde(default)]
+    pub allowlist_mode: AllowlistMode,
+    /// bonded units per unit of cw4 member weight, 1 if unset
+    #[serde(default)]
+    pub weight_divisor: Option<Uint128>,
+    /// DAO DAO core contract this contract serves voting power to
+    #[serde(default)]
+    pub dao: Option<String>,
+    /// whether unbonding LP still counts towards voting power
+    #[serde(default)]
+    pub count_unbonding_power: bool,
+    /// CW20 minted per share on bond and burned on unbond, set once it is instantiated
+    #[serde(default)]
+    pub receipt_token: Option<String>,
+    /// whether LP can be compounded into the pool, growing the value of a share
+    #[serde(default)]
+    pub share_mode: bool,
+    /// seconds over which withdrawn rewards vest, paid instantly if zero;
+    /// withdrawing again restarts the vesting of rewards still locked
+    #[serde(default)]
+    pub reward_vesting_duration: u64,
+    /// `(min staked seconds, multiplier)` tiers ascending by duration,
+    /// a multiplier of 1 applies below the first tier
+    #[serde(default)]
+    pub loyalty_multipliers: Vec<(u64, Decimal256)>,
+    /// cw721 collections whose NFTs can be locked, each with the boost
+    /// one NFT adds to the reward weight multiplier (0.1 for +10%)
+    #[serde(default)]
+    pub nft_boosts: Vec<(String, Decimal256)>,
+    /// cut of a referee's rewards diverted to its referrer
+    #[serde(default)]
+    pub referral_fee_bps: u64,
+}
+
+/// Who may bond; stakers dropped from the allowlist can still unbond and redeem
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum AllowlistMode {
+    /// Anyone may bond
+    #[default]
+    Disabled,
+    /// Addresses added through `AddToAllowlist`
+    Internal,
+    /// Members of a cw4 group contract
+    Cw4Group { group: String },
+}
+
+/// How rewards are emitted to stakers
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum EmissionMode {
+    /// Emit along `distribution_schedule` slots
+    #[default]
+    Schedule,
+    /// Emit a fixed amount per second until the funded reward balance runs out
+    Continuous { reward_per_second: Uint128 },
+}
+
+/// What happens to scheduled rewards emitted while nothing is bonded
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum IdleRewardPolicy {
+    /// Spread them over the rest of the distribution schedule
+    #[default]
+    RollForward,
+    /// Send them to `Config.treasury`
+    Treasury,
+    /// Credit them to the next staker
+    FirstStaker,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct State {
     pub last_distributed: u64,
     pub total_bond_amount: Uint128,
-    pub global_reward_index: Decimal,
+    /// cumulative reward per `REWARD_INDEX_SCALE` units of reward weight
+    pub global_reward_index: Decimal256,
+    /// funded rewards not yet emitted in continuous mode
+    #[serde(default)]
+    pub reward_balance: Uint128,
+    /// rewards emitted but not assignable through the index yet,
+    /// folded into the next distribution
+    #[serde(default)]
+    pub reward_dust: Uint128,
+    /// scheduled rewards emitted while nothing was bonded,
+    /// handled according to `Config.idle_reward_policy`
+    #[serde(default)]
+    pub stranded_reward: Uint128,
+    /// LP unbonded but not redeemed yet
+    #[serde(default)]
+    pub total_unbonding_amount: Uint128,
+    /// shares of `total_bond_amount`, equal to it unless rewards were compounded
+    #[serde(default)]
+    pub total_shares: Uint128,
+    /// withdrawn rewards vesting or vested but not claimed yet
+    #[serde(default)]
+    pub total_vesting_amount: Uint128,
+    /// sum of the stakers' reward weights
+    #[serde(default)]
+    pub total_reward_weight: Uint128,
+    /// referral rewards credited to referrers but not claimed yet
+    #[serde(default)]
+    pub total_referral_reward: Uint128,
+    /// rewards assigned through the index and not withdrawn yet, including the
+    /// referral cuts not credited yet; per staker rounding keeps it on the high side
+    #[serde(default)]
+    pub total_owed_reward: Uint128,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct StakerInfo {
     pub address: String,
-    pub reward_index: Decimal,
+    pub reward_index: Decimal256,
+    /// LP bonded, not counting what was compounded into its shares
     pub bond_amount: Uint128,
     pub pending_reward: Uint128,
+    /// stake in the pool
+    #[serde(default)]
+    pub shares: Uint128,
+    /// start of the uninterrupted stake the loyalty multiplier is based on
+    #[serde(default)]
+    pub staked_since: u64,
+    /// boosts of the locked NFTs, summed
+    #[serde(default)]
+    pub nft_boost: Decimal256,
+    /// shares times the loyalty multiplier and NFT boost,
+    /// rewards are earned per unit of weight
+    #[serde(default)]
+    pub reward_weight: Uint128,
+    /// recorded by the bond opening the position
+    #[serde(default)]
+    pub referrer: Option<String>,
+    /// rewards diverted to the referrer, not credited to it yet
+    #[serde(default)]
+    pub referral_reward: Uint128,
+}
+
+impl StakerInfo {
+    // empty position of `address`, before anything is bonded or locked
+    pub fn new(address: String) -> Self {
+        StakerInfo {
+            address,
+            reward_index: Decimal256::zero(),
+            bond_amount: Uint128::zero(),
+            pending_reward: Uint128::zero(),
+            shares: Uint128::zero(),
+            staked_since: 0,
+            nft_boost: Decimal256::zero(),
+            reward_weight: Uint128::zero(),
+            referrer: None,
+            referral_reward: Uint128::zero(),
+        }
+    }
 }
 
 pub type StakerInfoKey<'a> = String;
@@ -66,6 +277,29 @@ pub fn staker_info_storage<'a>(
     IndexedMap::new("staker_info", indexes)
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LockedNft {
+    pub token_id: String,
+    /// boost granted when locked, taken back on unlock
+    pub boost: Decimal256,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+pub struct ReferralEarnings {
+    pub pending: Uint128,
+    pub total: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+pub struct VestingInfo {
+    /// rewards still locked, released linearly from `start_time` to `end_time`
+    pub locked_amount: Uint128,
+    /// released rewards not claimed yet
+    pub vested_amount: Uint128,
+    pub start_time: u64,
+    pub end_time: u64,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct UserEarnedInfo {
     pub address: String,
@@ -116,11 +350,12 @@ pub fn unbonding_info_key<'a>(address: &String, time: u64) -> UnbondingInfoKey {
 
 pub struct UnbondingInfoIndicies<'a> {
     pub address: MultiIndex<'a, String, UnbondingInfo, UnbondingInfoKey<'a>>,
+    pub time: MultiIndex<'a, u64, UnbondingInfo, UnbondingInfoKey<'a>>,
 }
 
 impl<'a> IndexList<UnbondingInfo> for UnbondingInfoIndicies<'a> {
     fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<UnbondingInfo>> + '_> {
-        let v: Vec<&dyn Index<UnbondingInfo>> = vec![&self.address];
+        let v: Vec<&dyn Index<UnbondingInfo>> = vec![&self.address, &self.time];
         Box::new(v.into_iter())
     }
 }
@@ -133,6 +368,11 @@ pub fn unbonding_info_storage<'a>(
             "unbonding_info",
             "user_unbonding_info",
         ),
+        time: MultiIndex::new(
+            |d: &UnbondingInfo| d.time,
+            "unbonding_info",
+            "unbonding_info__time",
+        ),
     };
     IndexedMap::new("unbonding_info", indexes)
 }
@@ -1,23 +1,72 @@
 #[cfg(test)]
-use crate::contract::{execute, instantiate};
-use crate::msg::{Cw20HookMsg, ExecuteMsg, InstantiateMsg};
-use crate::query::{query_all_unbonding_info, query_staker_info, query_unbonding_info};
-use crate::state::Denom;
-use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
-use cosmwasm_std::{to_binary, Addr, BankMsg, Coin, CosmosMsg, DepsMut, Env, Uint128, WasmMsg};
+use crate::contract::{execute, instantiate, migrate, reply};
+use crate::error::ContractError;
+use crate::msg::{
+    Cw20HookMsg, ExecuteMsg, InfoResponse, InstantiateMsg, LockedNftEntry, LockedNftsResponse,
+    MigrateMsg, QueryMsg, ReceiptTokenInfo, ReferralEarningsResponse, ReferralsResponse,
+    ShareValueResponse, StakeChangedHookMsg,
+};
+use crate::query::{
+    query, query_all_unbonding, query_all_unbonding_info, query_allowlist, query_capacity,
+    query_claims, query_dust, query_list_members, query_member, query_reward_rate,
+    query_simulate_bond, query_simulate_rewards, query_simulate_unbond, query_simulate_withdraw,
+    query_solvency, query_staker_info, query_state, query_total_power_at_height,
+    query_total_weight, query_unbonding_info, query_vesting, query_voting_power_at_height,
+};
+use crate::state::{
+    staker_info_key, staker_info_storage, AllowlistMode, Denom, EmissionMode, IdleRewardPolicy,
+    STATE,
+};
+use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info, MockQuerier};
+use cosmwasm_std::{
+    from_binary, to_binary, Addr, BankMsg, Coin, ContractResult, CosmosMsg, Decimal, Decimal256,
+    Deps, DepsMut, Env, Reply, SubMsg, SubMsgExecutionResponse, SubMsgResult, SystemResult,
+    Timestamp, Uint128, WasmMsg, WasmQuery,
+};
 
-use cw20::{Cw20ExecuteMsg, Cw20ReceiveMsg};
+use cw20::{BalanceResponse, Cw20ExecuteMsg, Cw20QueryMsg, Cw20ReceiveMsg};
+use cw4::{Cw4QueryMsg, HooksResponse, Member, MemberResponse};
+use cw721::{Cw721ExecuteMsg, Cw721ReceiveMsg};
+use cw_controllers::HookError;
+use cw_storage_plus::{Index, IndexList, IndexedMap, MultiIndex};
+use serde::{Deserialize, Serialize};
 
-fn setup_contract(deps: DepsMut, env: Env) {
-    let instantiate_msg = InstantiateMsg {
+// instantiate message with every optional setting left unset, tests override what they exercise
+fn default_instantiate_msg() -> InstantiateMsg {
+    InstantiateMsg {
         lp_token_contract: "lp_token_contract".to_string(),
         reward_token: Denom::Native("ujuno".to_string()),
+        distribution_schedule: vec![],
+        lock_duration: 3600,
+        emission_mode: None,
+        treasury: None,
+        idle_reward_policy: None,
+        reward_fee_bps: None,
+        deposit_fee_bps: None,
+        min_bond_amount: None,
+        max_total_bond: None,
+        max_bond_per_address: None,
+        allowlist_mode: None,
+        weight_divisor: None,
+        dao: None,
+        count_unbonding_power: None,
+        receipt_token: None,
+        share_mode: None,
+        reward_vesting_duration: None,
+        loyalty_multipliers: None,
+        nft_boosts: None,
+        referral_fee_bps: None,
+    }
+}
+
+fn setup_contract(deps: DepsMut, env: Env) {
+    let instantiate_msg = InstantiateMsg {
         distribution_schedule: vec![(
             env.block.time.seconds(),
             env.block.time.seconds() + 86400,
             Uint128::new(100000000),
         )],
-        lock_duration: 3600,
+        ..default_instantiate_msg()
     };
     let info = mock_info("owner", &[]);
     let res = instantiate(deps, mock_env(), info, instantiate_msg).unwrap();
@@ -31,7 +80,7 @@ fn test_earned() {
     setup_contract(deps.as_mut(), env.clone());
 
     let info = mock_info("lp_token_contract", &[]);
-    let hook_msg = Cw20HookMsg::Bond {};
+    let hook_msg = Cw20HookMsg::Bond { referrer: None };
     let cw20_rcv_msg = Cw20ReceiveMsg {
         sender: "user1".to_string(),
         amount: Uint128::new(1000),
@@ -41,7 +90,7 @@ fn test_earned() {
     execute(deps.as_mut(), env.clone(), info, msg).unwrap();
 
     let info = mock_info("lp_token_contract", &[]);
-    let hook_msg = Cw20HookMsg::Bond {};
+    let hook_msg = Cw20HookMsg::Bond { referrer: None };
     let cw20_rcv_msg = Cw20ReceiveMsg {
         sender: "user2".to_string(),
         amount: Uint128::new(500),
@@ -84,7 +133,7 @@ fn test_unbond() {
     setup_contract(deps.as_mut(), env.clone());
 
     let info = mock_info("lp_token_contract", &[]);
-    let hook_msg = Cw20HookMsg::Bond {};
+    let hook_msg = Cw20HookMsg::Bond { referrer: None };
     let cw20_rcv_msg = Cw20ReceiveMsg {
         sender: "user1".to_string(),
         amount: Uint128::new(1000),
@@ -94,7 +143,7 @@ fn test_unbond() {
     execute(deps.as_mut(), env.clone(), info, msg).unwrap();
 
     let info = mock_info("lp_token_contract", &[]);
-    let hook_msg = Cw20HookMsg::Bond {};
+    let hook_msg = Cw20HookMsg::Bond { referrer: None };
     let cw20_rcv_msg = Cw20ReceiveMsg {
         sender: "user2".to_string(),
         amount: Uint128::new(500),
@@ -149,6 +198,40 @@ fn test_unbond() {
     println!("unbonding_info_after_redeem: {:?}", unbonding_info);
 }
 
+#[test]
+fn test_unbond_without_shares() {
+    let mut deps = mock_dependencies();
+    let mut env = mock_env();
+    setup_schedule(deps.as_mut(), env.clone(), 86400, 86400);
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("owner", &[]),
+        ExecuteMsg::UpdateNftBoosts {
+            nft_boosts: vec![("nft".to_string(), Decimal256::one())],
+        },
+    )
+    .unwrap();
+    bond_lp(deps.as_mut(), env.clone(), "user1", 1000);
+    lock_nft(deps.as_mut(), env.clone(), "nft", "user2").unwrap();
+    env.block.time = env.block.time.plus_seconds(100);
+
+    // the position is kept for its pending reward
+    let unbond = |amount: u128| ExecuteMsg::Unbond {
+        amount: Uint128::new(amount),
+    };
+    let info = mock_info("user1", &[]);
+    execute(deps.as_mut(), env.clone(), info.clone(), unbond(1000)).unwrap();
+    let staker_info = query_staker_info(deps.as_ref(), env.clone(), "user1".to_string()).unwrap();
+    assert_eq!(staker_info.shares, Uint128::zero());
+    assert_eq!(staker_info.pending_reward, Uint128::new(100));
+
+    for info in [info, mock_info("user2", &[])] {
+        let err = execute(deps.as_mut(), env.clone(), info, unbond(0)).unwrap_err();
+        assert!(matches!(err, ContractError::ExceedBondAmount {}));
+    }
+}
+
 #[test]
 fn test_withdraw() {
     let mut deps = mock_dependencies();
@@ -156,7 +239,7 @@ fn test_withdraw() {
     setup_contract(deps.as_mut(), env.clone());
 
     let info = mock_info("lp_token_contract", &[]);
-    let hook_msg = Cw20HookMsg::Bond {};
+    let hook_msg = Cw20HookMsg::Bond { referrer: None };
     let cw20_rcv_msg = Cw20ReceiveMsg {
         sender: "user1".to_string(),
         amount: Uint128::new(1000),
@@ -198,3 +281,2371 @@ fn test_withdraw() {
         })
     )
 }
+
+#[test]
+fn test_continuous_emission() {
+    let mut deps = mock_dependencies();
+    let mut env = mock_env();
+    let instantiate_msg = InstantiateMsg {
+        emission_mode: Some(EmissionMode::Continuous {
+            reward_per_second: Uint128::new(10),
+        }),
+        ..default_instantiate_msg()
+    };
+    instantiate(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("owner", &[]),
+        instantiate_msg,
+    )
+    .unwrap();
+
+    let info = mock_info("owner", &[Coin::new(1000, "ujuno")]);
+    execute(deps.as_mut(), env.clone(), info, ExecuteMsg::FundRewards {}).unwrap();
+
+    let info = mock_info("lp_token_contract", &[]);
+    let cw20_rcv_msg = Cw20ReceiveMsg {
+        sender: "user1".to_string(),
+        amount: Uint128::new(1000),
+        msg: to_binary(&Cw20HookMsg::Bond { referrer: None }).unwrap(),
+    };
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        info,
+        ExecuteMsg::Receive(cw20_rcv_msg),
+    )
+    .unwrap();
+
+    env.block.time = env.block.time.plus_seconds(50);
+    let staker1_info = query_staker_info(deps.as_ref(), env.clone(), "user1".to_string()).unwrap();
+    assert_eq!(staker1_info.pending_reward, Uint128::new(500));
+
+    // rate change settles the first 50 seconds at the old rate
+    let msg = ExecuteMsg::UpdateEmissionMode {
+        emission_mode: EmissionMode::Continuous {
+            reward_per_second: Uint128::new(20),
+        },
+    };
+    execute(deps.as_mut(), env.clone(), mock_info("owner", &[]), msg).unwrap();
+
+    env.block.time = env.block.time.plus_seconds(10);
+    let staker1_info = query_staker_info(deps.as_ref(), env.clone(), "user1".to_string()).unwrap();
+    assert_eq!(staker1_info.pending_reward, Uint128::new(700));
+
+    // emission stops once the funded balance is exhausted
+    env.block.time = env.block.time.plus_seconds(1000);
+    let staker1_info = query_staker_info(deps.as_ref(), env.clone(), "user1".to_string()).unwrap();
+    assert_eq!(staker1_info.pending_reward, Uint128::new(1000));
+
+    let state = query_state(deps.as_ref(), Some(env.block.time.seconds())).unwrap();
+    assert_eq!(state.reward_balance, Uint128::zero());
+}
+
+fn bond_lp(deps: DepsMut, env: Env, staker: &str, amount: u128) {
+    let info = mock_info("lp_token_contract", &[]);
+    let cw20_rcv_msg = Cw20ReceiveMsg {
+        sender: staker.to_string(),
+        amount: Uint128::new(amount),
+        msg: to_binary(&Cw20HookMsg::Bond { referrer: None }).unwrap(),
+    };
+    execute(deps, env, info, ExecuteMsg::Receive(cw20_rcv_msg)).unwrap();
+}
+
+fn setup_schedule(deps: DepsMut, env: Env, duration: u64, amount: u128) {
+    let instantiate_msg = InstantiateMsg {
+        distribution_schedule: vec![(
+            env.block.time.seconds(),
+            env.block.time.seconds() + duration,
+            Uint128::new(amount),
+        )],
+        ..default_instantiate_msg()
+    };
+    instantiate(deps, env, mock_info("owner", &[]), instantiate_msg).unwrap();
+}
+
+#[test]
+fn test_tiny_reward_huge_stake() {
+    let mut deps = mock_dependencies();
+    let mut env = mock_env();
+    // 1 reward unit per second
+    setup_schedule(deps.as_mut(), env.clone(), 86400, 86400);
+
+    // 1M and 3M LP with 18 decimals
+    bond_lp(
+        deps.as_mut(),
+        env.clone(),
+        "user1",
+        1_000_000_000_000_000_000_000_000,
+    );
+    bond_lp(
+        deps.as_mut(),
+        env.clone(),
+        "user2",
+        3_000_000_000_000_000_000_000_000,
+    );
+
+    env.block.time = env.block.time.plus_seconds(100);
+    let staker1_info = query_staker_info(deps.as_ref(), env.clone(), "user1".to_string()).unwrap();
+    let staker2_info = query_staker_info(deps.as_ref(), env.clone(), "user2".to_string()).unwrap();
+    assert_eq!(staker1_info.pending_reward, Uint128::new(25));
+    assert_eq!(staker2_info.pending_reward, Uint128::new(75));
+}
+
+#[test]
+fn test_huge_reward_tiny_stake() {
+    let mut deps = mock_dependencies();
+    let mut env = mock_env();
+    setup_schedule(
+        deps.as_mut(),
+        env.clone(),
+        1000,
+        1_000_000_000_000_000_000_000_000_000_000,
+    );
+
+    bond_lp(deps.as_mut(), env.clone(), "user1", 1);
+
+    env.block.time = env.block.time.plus_seconds(1000);
+    let staker1_info = query_staker_info(deps.as_ref(), env.clone(), "user1".to_string()).unwrap();
+    assert_eq!(
+        staker1_info.pending_reward,
+        Uint128::new(1_000_000_000_000_000_000_000_000_000_000)
+    );
+}
+
+// staker and unbonding layouts of 0.1.0: `Decimal` indices, no shares or weights,
+// and unbonding entries indexed by address only
+#[derive(Serialize, Deserialize, Clone)]
+struct StakerInfoV010 {
+    address: String,
+    reward_index: Decimal,
+    bond_amount: Uint128,
+    pending_reward: Uint128,
+}
+
+struct StakerInfoIndiciesV010<'a> {
+    address: MultiIndex<'a, String, StakerInfoV010, String>,
+}
+
+impl<'a> IndexList<StakerInfoV010> for StakerInfoIndiciesV010<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<StakerInfoV010>> + '_> {
+        let v: Vec<&dyn Index<StakerInfoV010>> = vec![&self.address];
+        Box::new(v.into_iter())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct UnbondingInfoV010 {
+    address: String,
+    time: u64,
+    amount: Uint128,
+}
+
+struct UnbondingInfoIndiciesV010<'a> {
+    address: MultiIndex<'a, String, UnbondingInfoV010, (String, u64)>,
+}
+
+impl<'a> IndexList<UnbondingInfoV010> for UnbondingInfoIndiciesV010<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<UnbondingInfoV010>> + '_> {
+        let v: Vec<&dyn Index<UnbondingInfoV010>> = vec![&self.address];
+        Box::new(v.into_iter())
+    }
+}
+
+fn setup_v010_store(deps: DepsMut, env: Env) {
+    let now = env.block.time.seconds();
+    cw2::set_contract_version(deps.storage, "Hopers_Staking", "0.1.0").unwrap();
+    deps.storage.set(
+        b"config_config",
+        format!(
+            r#"{{"lp_token_contract":"lp_token_contract","reward_token":{{"native":"ujuno"}},"distribution_schedule":[[{},{},"86400"]],"admin":"owner","lock_duration":3600}}"#,
+            now,
+            now + 86400
+        )
+        .as_bytes(),
+    );
+    deps.storage.set(
+        b"config_state",
+        format!(
+            r#"{{"last_distributed":{},"total_bond_amount":"1500","global_reward_index":"0.5"}}"#,
+            now
+        )
+        .as_bytes(),
+    );
+
+    let stakers = IndexedMap::new(
+        "staker_info",
+        StakerInfoIndiciesV010 {
+            address: MultiIndex::new(
+                |d: &StakerInfoV010| d.address.clone(),
+                "staker_info",
+                "staker_info__collection",
+            ),
+        },
+    );
+    for (address, reward_index, bond_amount, pending_reward) in
+        [("user1", 25u64, 1000u128, 0u128), ("user2", 50, 500, 10)]
+    {
+        let staker_info = StakerInfoV010 {
+            address: address.to_string(),
+            reward_index: Decimal::percent(reward_index),
+            bond_amount: Uint128::new(bond_amount),
+            pending_reward: Uint128::new(pending_reward),
+        };
+        stakers
+            .save(deps.storage, address.to_string(), &staker_info)
+            .unwrap();
+    }
+
+    let unbondings = IndexedMap::new(
+        "unbonding_info",
+        UnbondingInfoIndiciesV010 {
+            address: MultiIndex::new(
+                |d: &UnbondingInfoV010| d.address.clone(),
+                "unbonding_info",
+                "user_unbonding_info",
+            ),
+        },
+    );
+    for (address, time, amount) in [("user1", now - 100, 200u128), ("user2", now - 50, 100)] {
+        let unbonding_info = UnbondingInfoV010 {
+            address: address.to_string(),
+            time,
+            amount: Uint128::new(amount),
+        };
+        unbondings
+            .save(deps.storage, (address.to_string(), time), &unbonding_info)
+            .unwrap();
+    }
+}
+
+#[test]
+fn test_migrate_reward_indices() {
+    let mut deps = mock_dependencies();
+    let mut env = mock_env();
+    let now = env.block.time.seconds();
+    setup_v010_store(deps.as_mut(), env.clone());
+
+    migrate(deps.as_mut(), env.clone(), MigrateMsg {}).unwrap();
+
+    let state = STATE.load(deps.as_ref().storage).unwrap();
+    assert_eq!(
+        state.global_reward_index,
+        Decimal256::from_ratio(500_000_000_000_000_000u128, 1u128)
+    );
+    assert_eq!(state.total_shares, Uint128::new(1500));
+    assert_eq!(state.total_unbonding_amount, Uint128::new(300));
+    let staker_info = staker_info_storage()
+        .load(deps.as_ref().storage, staker_info_key(&"user1".to_string()))
+        .unwrap();
+    assert_eq!(
+        staker_info.reward_index,
+        Decimal256::from_ratio(250_000_000_000_000_000u128, 1u128)
+    );
+    assert_eq!(staker_info.shares, Uint128::new(1000));
+    let staker1_info = query_staker_info(deps.as_ref(), env.clone(), "user1".to_string()).unwrap();
+    assert_eq!(staker1_info.pending_reward, Uint128::new(250));
+
+    // the old unbonding entries are indexed by time
+    let res = query_all_unbonding(deps.as_ref(), None, None).unwrap();
+    assert_eq!(res.total_unbonding_amount, Uint128::new(300));
+    assert_eq!(
+        res.entries
+            .iter()
+            .map(|entry| (entry.address.as_str(), entry.time))
+            .collect::<Vec<_>>(),
+        vec![("user1", now - 100), ("user2", now - 50)]
+    );
+    assert_eq!(
+        query_member(deps.as_ref(), "user2".to_string(), None)
+            .unwrap()
+            .weight,
+        Some(500)
+    );
+
+    let info = mock_info("user1", &[]);
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info.clone(),
+        ExecuteMsg::Withdraw {},
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages[0].msg,
+        CosmosMsg::Bank(BankMsg::Send {
+            to_address: "user1".to_string(),
+            amount: vec![Coin {
+                denom: "ujuno".to_string(),
+                amount: Uint128::new(250),
+            }],
+        })
+    );
+    let msg = ExecuteMsg::Unbond {
+        amount: Uint128::new(400),
+    };
+    execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+    assert_eq!(
+        query_member(deps.as_ref(), "user1".to_string(), None)
+            .unwrap()
+            .weight,
+        Some(600)
+    );
+
+    // the migrated entry is redeemed along with the new one
+    env.block.time = env.block.time.plus_seconds(3600);
+    let res = execute(deps.as_mut(), env, info, ExecuteMsg::Redeem {}).unwrap();
+    assert_eq!(
+        res.messages[0].msg,
+        CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "lp_token_contract".to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: "user1".to_string(),
+                amount: Uint128::new(600),
+            })
+            .unwrap(),
+            funds: vec![],
+        })
+    );
+}
+
+#[test]
+fn test_schedule_emits_exact_amount() {
+    let mut deps = mock_dependencies();
+    let mut env = mock_env();
+    setup_schedule(deps.as_mut(), env.clone(), 3, 100);
+    bond_lp(deps.as_mut(), env.clone(), "user1", 1);
+
+    // settle every second, which used to truncate 33.33.. three times
+    let mut withdrawn = Uint128::zero();
+    for _ in 0..3 {
+        env.block.time = env.block.time.plus_seconds(1);
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("user1", &[]),
+            ExecuteMsg::Withdraw {},
+        )
+        .unwrap();
+        withdrawn += res.attributes[2].value.parse::<Uint128>().unwrap();
+    }
+
+    assert_eq!(withdrawn, Uint128::new(100));
+}
+
+#[test]
+fn test_dust() {
+    let mut deps = mock_dependencies();
+    let mut env = mock_env();
+    setup_schedule(deps.as_mut(), env.clone(), 10, 10);
+    bond_lp(
+        deps.as_mut(),
+        env.clone(),
+        "user1",
+        2_000_000_000_000_000_000_000_000_000_000_000_000,
+    );
+
+    // one unit over a bond beyond the index precision cannot be assigned
+    env.block.time = env.block.time.plus_seconds(1);
+    let dust = query_dust(deps.as_ref(), env.clone()).unwrap();
+    assert_eq!(dust.dust, Uint128::new(1));
+
+    // and is folded into the next distribution
+    env.block.time = env.block.time.plus_seconds(1);
+    let dust = query_dust(deps.as_ref(), env.clone()).unwrap();
+    assert_eq!(dust.dust, Uint128::zero());
+    let staker1_info = query_staker_info(deps.as_ref(), env.clone(), "user1".to_string()).unwrap();
+    assert_eq!(staker1_info.pending_reward, Uint128::new(2));
+
+    env.block.time = env.block.time.plus_seconds(1);
+    let msg = ExecuteMsg::SweepDust {
+        recipient: "treasury".to_string(),
+    };
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("user1", &[]),
+        msg.clone(),
+    );
+    assert!(err.is_err());
+    let res = execute(deps.as_mut(), env.clone(), mock_info("owner", &[]), msg).unwrap();
+    assert_eq!(
+        res.messages[0].msg,
+        CosmosMsg::Bank(BankMsg::Send {
+            to_address: "treasury".to_string(),
+            amount: vec![Coin::new(1, "ujuno")]
+        })
+    );
+
+    let state = query_state(deps.as_ref(), None).unwrap();
+    assert_eq!(state.reward_dust, Uint128::zero());
+    let staker1_info = query_staker_info(deps.as_ref(), env, "user1".to_string()).unwrap();
+    assert_eq!(staker1_info.pending_reward, Uint128::new(2));
+}
+
+fn setup_idle_reward_policy(deps: DepsMut, env: Env, idle_reward_policy: IdleRewardPolicy) {
+    let instantiate_msg = InstantiateMsg {
+        distribution_schedule: vec![(
+            env.block.time.seconds(),
+            env.block.time.seconds() + 100,
+            Uint128::new(1000),
+        )],
+        treasury: Some("treasury".to_string()),
+        idle_reward_policy: Some(idle_reward_policy),
+        ..default_instantiate_msg()
+    };
+    instantiate(deps, env, mock_info("owner", &[]), instantiate_msg).unwrap();
+}
+
+#[test]
+fn test_idle_rewards_roll_forward() {
+    let mut deps = mock_dependencies();
+    let mut env = mock_env();
+    setup_idle_reward_policy(deps.as_mut(), env.clone(), IdleRewardPolicy::RollForward);
+
+    // nobody bonded during the first half of the schedule
+    env.block.time = env.block.time.plus_seconds(50);
+    bond_lp(deps.as_mut(), env.clone(), "user1", 1000);
+    let state = query_state(deps.as_ref(), None).unwrap();
+    assert_eq!(state.stranded_reward, Uint128::new(500));
+
+    env.block.time = env.block.time.plus_seconds(25);
+    let staker1_info = query_staker_info(deps.as_ref(), env.clone(), "user1".to_string()).unwrap();
+    assert_eq!(staker1_info.pending_reward, Uint128::new(500));
+
+    env.block.time = env.block.time.plus_seconds(25);
+    let staker1_info = query_staker_info(deps.as_ref(), env.clone(), "user1".to_string()).unwrap();
+    assert_eq!(staker1_info.pending_reward, Uint128::new(1000));
+}
+
+#[test]
+fn test_idle_rewards_first_staker() {
+    let mut deps = mock_dependencies();
+    let mut env = mock_env();
+    setup_idle_reward_policy(deps.as_mut(), env.clone(), IdleRewardPolicy::FirstStaker);
+
+    env.block.time = env.block.time.plus_seconds(50);
+    bond_lp(deps.as_mut(), env.clone(), "user1", 1000);
+    bond_lp(deps.as_mut(), env.clone(), "user2", 1000);
+
+    let staker1_info = query_staker_info(deps.as_ref(), env.clone(), "user1".to_string()).unwrap();
+    let staker2_info = query_staker_info(deps.as_ref(), env.clone(), "user2".to_string()).unwrap();
+    assert_eq!(staker1_info.pending_reward, Uint128::new(500));
+    assert_eq!(staker2_info.pending_reward, Uint128::zero());
+}
+
+#[test]
+fn test_idle_rewards_treasury() {
+    let mut deps = mock_dependencies();
+    let mut env = mock_env();
+    setup_idle_reward_policy(deps.as_mut(), env.clone(), IdleRewardPolicy::Treasury);
+
+    env.block.time = env.block.time.plus_seconds(50);
+    let info = mock_info("lp_token_contract", &[]);
+    let cw20_rcv_msg = Cw20ReceiveMsg {
+        sender: "user1".to_string(),
+        amount: Uint128::new(1000),
+        msg: to_binary(&Cw20HookMsg::Bond { referrer: None }).unwrap(),
+    };
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info,
+        ExecuteMsg::Receive(cw20_rcv_msg),
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages[0].msg,
+        CosmosMsg::Bank(BankMsg::Send {
+            to_address: "treasury".to_string(),
+            amount: vec![Coin::new(500, "ujuno")]
+        })
+    );
+
+    env.block.time = env.block.time.plus_seconds(50);
+    let staker1_info = query_staker_info(deps.as_ref(), env, "user1".to_string()).unwrap();
+    assert_eq!(staker1_info.pending_reward, Uint128::new(500));
+    let state = query_state(deps.as_ref(), None).unwrap();
+    assert_eq!(state.stranded_reward, Uint128::zero());
+}
+
+#[test]
+fn test_invalid_schedule() {
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+    let instantiate_msg = InstantiateMsg {
+        distribution_schedule: vec![(100, 100, Uint128::new(1000))],
+        ..default_instantiate_msg()
+    };
+    let err = instantiate(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("owner", &[]),
+        instantiate_msg,
+    )
+    .unwrap_err();
+    assert!(matches!(
+        err,
+        ContractError::InvalidSchedule {
+            start: 100,
+            end: 100
+        }
+    ));
+
+    setup_contract(deps.as_mut(), env.clone());
+    let msg = ExecuteMsg::UpdateConfig {
+        distribution_schedule: vec![
+            (
+                env.block.time.seconds(),
+                env.block.time.seconds() + 86400,
+                Uint128::new(100000000),
+            ),
+            (200, 100, Uint128::new(1000)),
+        ],
+    };
+    let err = execute(deps.as_mut(), env, mock_info("owner", &[]), msg).unwrap_err();
+    assert!(matches!(err, ContractError::InvalidSchedule { .. }));
+}
+
+#[test]
+fn test_reward_overflow() {
+    let mut deps = mock_dependencies();
+    let mut env = mock_env();
+    let instantiate_msg = InstantiateMsg {
+        emission_mode: Some(EmissionMode::Continuous {
+            reward_per_second: Uint128::MAX,
+        }),
+        ..default_instantiate_msg()
+    };
+    instantiate(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("owner", &[]),
+        instantiate_msg,
+    )
+    .unwrap();
+    bond_lp(deps.as_mut(), env.clone(), "user1", 1000);
+
+    env.block.time = env.block.time.plus_seconds(2);
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("user1", &[]),
+        ExecuteMsg::Withdraw {},
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::Overflow(_)));
+
+    // queries surface the same failure instead of aborting
+    assert!(query_staker_info(deps.as_ref(), env, "user1".to_string()).is_err());
+}
+
+#[test]
+fn test_reward_rate() {
+    let mut deps = mock_dependencies();
+    let mut env = mock_env();
+    let now = env.block.time.seconds();
+    let instantiate_msg = InstantiateMsg {
+        distribution_schedule: vec![
+            (now, now + 86400, Uint128::new(86400)),
+            (now + 100, now + 200, Uint128::new(1000)),
+        ],
+        ..default_instantiate_msg()
+    };
+    instantiate(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("owner", &[]),
+        instantiate_msg,
+    )
+    .unwrap();
+    bond_lp(
+        deps.as_mut(),
+        env.clone(),
+        "user1",
+        2_000_000_000_000_000_000,
+    );
+
+    let rate = query_reward_rate(deps.as_ref(), env.clone(), Some(now + 150)).unwrap();
+    assert_eq!(
+        rate.reward_per_second,
+        Decimal256::from_ratio(11u128, 1u128)
+    );
+    assert_eq!(
+        rate.reward_per_unit_per_second,
+        Decimal256::from_ratio(11u128, 2u128)
+    );
+    assert_eq!(
+        rate.annualized_reward_per_unit,
+        Decimal256::from_ratio(173_448_000u128, 1u128)
+    );
+
+    let rate = query_reward_rate(deps.as_ref(), env.clone(), Some(now + 300)).unwrap();
+    assert_eq!(rate.reward_per_second, Decimal256::one());
+    assert_eq!(rate.reward_per_unit_per_second, Decimal256::percent(50));
+
+    // a boosted staker weighs more, so each unit of weight earns less
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("owner", &[]),
+        ExecuteMsg::UpdateNftBoosts {
+            nft_boosts: vec![("nft".to_string(), Decimal256::one())],
+        },
+    )
+    .unwrap();
+    lock_nft(deps.as_mut(), env.clone(), "nft", "user1").unwrap();
+    let rate = query_reward_rate(deps.as_ref(), env.clone(), Some(now + 300)).unwrap();
+    assert_eq!(
+        rate.total_reward_weight,
+        Uint128::new(4_000_000_000_000_000_000)
+    );
+    assert_eq!(rate.reward_per_unit_per_second, Decimal256::percent(25));
+
+    // the rate times the staker's weight is what it accrues
+    env.block.time = env.block.time.plus_seconds(300);
+    let before = query_staker_info(deps.as_ref(), env.clone(), "user1".to_string()).unwrap();
+    env.block.time = env.block.time.plus_seconds(100);
+    let after = query_staker_info(deps.as_ref(), env, "user1".to_string()).unwrap();
+    assert_eq!(
+        after
+            .pending_reward
+            .checked_sub(before.pending_reward)
+            .unwrap(),
+        Uint128::new(100)
+    );
+}
+
+#[test]
+fn test_simulate_rewards() {
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+    setup_schedule(deps.as_mut(), env.clone(), 86400, 86400);
+    bond_lp(deps.as_mut(), env.clone(), "user1", 1000);
+
+    // a new staker dilutes user1
+    let res =
+        query_simulate_rewards(deps.as_ref(), env.clone(), Uint128::new(3000), 100, None).unwrap();
+    assert_eq!(res.bond_amount, Uint128::new(3000));
+    assert_eq!(res.total_bond_amount, Uint128::new(4000));
+    assert_eq!(res.projected_reward, Uint128::new(75));
+
+    // user1 topping up keeps the whole emission
+    let res = query_simulate_rewards(
+        deps.as_ref(),
+        env.clone(),
+        Uint128::new(1000),
+        100,
+        Some("user1".to_string()),
+    )
+    .unwrap();
+    assert_eq!(res.bond_amount, Uint128::new(2000));
+    assert_eq!(res.projected_reward, Uint128::new(100));
+
+    // projection stops with the schedule
+    let res = query_simulate_rewards(
+        deps.as_ref(),
+        env,
+        Uint128::zero(),
+        200000,
+        Some("user1".to_string()),
+    )
+    .unwrap();
+    assert_eq!(res.projected_reward, Uint128::new(86400));
+}
+
+#[test]
+fn test_simulate_operations() {
+    let mut deps = mock_dependencies();
+    let mut env = mock_env();
+    setup_schedule(deps.as_mut(), env.clone(), 86400, 86400);
+    bond_lp(deps.as_mut(), env.clone(), "user1", 1000);
+    env.block.time = env.block.time.plus_seconds(100);
+
+    let res = query_simulate_bond(
+        deps.as_ref(),
+        env.clone(),
+        "user1".to_string(),
+        Uint128::new(500),
+    )
+    .unwrap();
+    assert_eq!(res.error, None);
+    assert_eq!(res.bond_amount, Uint128::new(1500));
+    assert_eq!(res.pending_reward, Uint128::new(100));
+    assert_eq!(res.total_bond_amount, Uint128::new(1500));
+
+    let res = query_simulate_unbond(
+        deps.as_ref(),
+        env.clone(),
+        "user1".to_string(),
+        Uint128::new(400),
+    )
+    .unwrap();
+    assert_eq!(res.error, None);
+    assert_eq!(res.bond_amount, Uint128::new(600));
+    assert_eq!(res.pending_reward, Uint128::new(100));
+
+    let res = query_simulate_unbond(
+        deps.as_ref(),
+        env.clone(),
+        "user1".to_string(),
+        Uint128::new(1001),
+    )
+    .unwrap();
+    assert_eq!(
+        res.error,
+        Some(ContractError::ExceedBondAmount {}.to_string())
+    );
+    assert_eq!(res.bond_amount, Uint128::new(1000));
+
+    let res = query_simulate_withdraw(deps.as_ref(), env.clone(), "user2".to_string()).unwrap();
+    assert_eq!(res.error, Some(ContractError::NotStaked {}.to_string()));
+
+    // the dry run matches the real withdrawal
+    let res = query_simulate_withdraw(deps.as_ref(), env.clone(), "user1".to_string()).unwrap();
+    assert_eq!(res.withdrawn_reward, Uint128::new(100));
+    assert_eq!(res.pending_reward, Uint128::zero());
+
+    let info = mock_info("user1", &[]);
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info.clone(),
+        ExecuteMsg::Withdraw {},
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages[0].msg,
+        CosmosMsg::Bank(BankMsg::Send {
+            to_address: "user1".to_string(),
+            amount: vec![Coin {
+                denom: "ujuno".to_string(),
+                amount: Uint128::new(100),
+            }],
+        })
+    );
+
+    // under vesting nothing is paid out, the reward moves into vesting instead
+    let msg = ExecuteMsg::UpdateRewardVestingDuration {
+        reward_vesting_duration: 1000,
+    };
+    execute(deps.as_mut(), env.clone(), mock_info("owner", &[]), msg).unwrap();
+    env.block.time = env.block.time.plus_seconds(100);
+    let res = query_simulate_withdraw(deps.as_ref(), env.clone(), "user1".to_string()).unwrap();
+    assert_eq!(res.error, None);
+    assert_eq!(res.withdrawn_reward, Uint128::zero());
+    assert_eq!(res.vesting_reward, Uint128::new(100));
+
+    let res = execute(deps.as_mut(), env.clone(), info, ExecuteMsg::Withdraw {}).unwrap();
+    assert!(res.messages.is_empty());
+    let res = query_vesting(deps.as_ref(), env, "user1".to_string()).unwrap();
+    assert_eq!(res.locked_amount, Uint128::new(100));
+}
+
+#[test]
+fn test_claims() {
+    let mut deps = mock_dependencies();
+    let mut env = mock_env();
+    let now = env.block.time.seconds();
+    setup_schedule(deps.as_mut(), env.clone(), 86400, 86400);
+    bond_lp(deps.as_mut(), env.clone(), "user1", 1000);
+
+    let info = mock_info("user1", &[]);
+    for (offset, amount) in [(0, 100u128), (1000, 200), (2000, 300)] {
+        env.block.time = env.block.time.plus_seconds(offset);
+        let msg = ExecuteMsg::Unbond {
+            amount: Uint128::new(amount),
+        };
+        execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+    }
+
+    // lock_duration is 3600, the first entry unlocks at now + 3600
+    env.block.time = Timestamp::from_seconds(now + 4000);
+    let res = query_claims(deps.as_ref(), env.clone(), "user1".to_string()).unwrap();
+    assert_eq!(res.redeemable_amount, Uint128::new(100));
+    assert_eq!(res.locked_amount, Uint128::new(500));
+    assert_eq!(res.next_release_at, Some(now + 1000 + 3600));
+    assert_eq!(res.claims.len(), 3);
+    assert_eq!(res.claims[2].time, now + 3000);
+    assert_eq!(res.claims[2].release_at, now + 3000 + 3600);
+
+    // everything redeemable once the last entry is released
+    env.block.time = Timestamp::from_seconds(now + 3000 + 3600);
+    let res = query_claims(deps.as_ref(), env, "user1".to_string()).unwrap();
+    assert_eq!(res.redeemable_amount, Uint128::new(600));
+    assert_eq!(res.locked_amount, Uint128::zero());
+    assert_eq!(res.next_release_at, None);
+}
+
+#[test]
+fn test_all_unbonding() {
+    let mut deps = mock_dependencies();
+    let mut env = mock_env();
+    let now = env.block.time.seconds();
+    setup_schedule(deps.as_mut(), env.clone(), 86400, 86400);
+    bond_lp(deps.as_mut(), env.clone(), "user1", 1000);
+    bond_lp(deps.as_mut(), env.clone(), "user2", 1000);
+
+    let unbond = |deps: DepsMut, env: Env, staker: &str, amount: u128| {
+        let msg = ExecuteMsg::Unbond {
+            amount: Uint128::new(amount),
+        };
+        execute(deps, env, mock_info(staker, &[]), msg).unwrap();
+    };
+    unbond(deps.as_mut(), env.clone(), "user2", 100);
+    unbond(deps.as_mut(), env.clone(), "user2", 50);
+    env.block.time = Timestamp::from_seconds(now + 10);
+    unbond(deps.as_mut(), env.clone(), "user1", 200);
+    env.block.time = Timestamp::from_seconds(now + 20);
+    unbond(deps.as_mut(), env.clone(), "user2", 300);
+
+    // unbonds within one block are merged, entries come in release order
+    let res = query_all_unbonding(deps.as_ref(), None, Some(2)).unwrap();
+    assert_eq!(res.total_unbonding_amount, Uint128::new(650));
+    assert_eq!(res.entries.len(), 2);
+    assert_eq!(res.entries[0].address, "user2");
+    assert_eq!(res.entries[0].amount, Uint128::new(150));
+    assert_eq!(res.entries[0].release_at, now + 3600);
+    assert_eq!(res.entries[1].address, "user1");
+
+    let res =
+        query_all_unbonding(deps.as_ref(), Some((now + 10, "user1".to_string())), None).unwrap();
+    assert_eq!(res.entries.len(), 1);
+    assert_eq!(res.entries[0].amount, Uint128::new(300));
+    assert_eq!(res.entries[0].time, now + 20);
+
+    env.block.time = Timestamp::from_seconds(now + 3600);
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("user2", &[]),
+        ExecuteMsg::Redeem {},
+    )
+    .unwrap();
+    let state = query_state(deps.as_ref(), None).unwrap();
+    assert_eq!(state.total_unbonding_amount, Uint128::new(500));
+
+    // 0.1.0 stores had neither the total nor the time index
+    cw2::set_contract_version(deps.as_mut().storage, "Hopers_Staking", "0.1.0").unwrap();
+    let mut state = STATE.load(deps.as_ref().storage).unwrap();
+    state.total_unbonding_amount = Uint128::zero();
+    STATE.save(deps.as_mut().storage, &state).unwrap();
+    migrate(deps.as_mut(), env, MigrateMsg {}).unwrap();
+    let state = query_state(deps.as_ref(), None).unwrap();
+    assert_eq!(state.total_unbonding_amount, Uint128::new(500));
+}
+
+fn mock_lp_balance(querier: &mut MockQuerier, amount: u128) {
+    querier.update_wasm(move |query| match query {
+        WasmQuery::Smart { contract_addr, msg } if contract_addr == "lp_token_contract" => {
+            match from_binary(msg).unwrap() {
+                Cw20QueryMsg::Balance { .. } => SystemResult::Ok(ContractResult::Ok(
+                    to_binary(&BalanceResponse {
+                        balance: Uint128::new(amount),
+                    })
+                    .unwrap(),
+                )),
+                _ => panic!("unexpected cw20 query"),
+            }
+        }
+        _ => panic!("unexpected wasm query"),
+    });
+}
+
+#[test]
+fn test_solvency() {
+    let mut deps = mock_dependencies();
+    let mut env = mock_env();
+    setup_schedule(deps.as_mut(), env.clone(), 86400, 86400);
+    bond_lp(deps.as_mut(), env.clone(), "user1", 1000);
+    env.block.time = env.block.time.plus_seconds(100);
+
+    mock_lp_balance(&mut deps.querier, 1000);
+    deps.querier.update_balance(
+        env.contract.address.clone(),
+        vec![Coin::new(86400, "ujuno")],
+    );
+    let res = query_solvency(deps.as_ref(), env.clone()).unwrap();
+    assert_eq!(res.lp_required, Uint128::new(1000));
+    assert_eq!(res.owed_reward, Uint128::new(100));
+    assert_eq!(res.remaining_reward, Uint128::new(86300));
+    assert_eq!(res.reward_required, Uint128::new(86400));
+    assert_eq!(res.reward_surplus, Uint128::zero());
+    assert!(res.solvent);
+
+    // unbonding LP is still owed
+    let msg = ExecuteMsg::Unbond {
+        amount: Uint128::new(400),
+    };
+    execute(deps.as_mut(), env.clone(), mock_info("user1", &[]), msg).unwrap();
+    mock_lp_balance(&mut deps.querier, 900);
+    deps.querier.update_balance(
+        env.contract.address.clone(),
+        vec![Coin::new(90000, "ujuno")],
+    );
+    let res = query_solvency(deps.as_ref(), env.clone()).unwrap();
+    assert_eq!(res.lp_required, Uint128::new(1000));
+    assert_eq!(res.lp_deficit, Uint128::new(100));
+    assert_eq!(res.reward_surplus, Uint128::new(3600));
+    assert!(!res.solvent);
+
+    // withdrawn rewards leave the running total of owed rewards
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("user1", &[]),
+        ExecuteMsg::Withdraw {},
+    )
+    .unwrap();
+    let res = query_solvency(deps.as_ref(), env).unwrap();
+    assert_eq!(res.owed_reward, Uint128::zero());
+}
+
+#[test]
+fn test_sweep() {
+    let mut deps = mock_dependencies();
+    let mut env = mock_env();
+    setup_schedule(deps.as_mut(), env.clone(), 86400, 86400);
+    bond_lp(deps.as_mut(), env.clone(), "user1", 1000);
+    env.block.time = env.block.time.plus_seconds(100);
+
+    // 500 LP and 3600 ujuno sent by mistake, plus an unrelated token
+    mock_lp_balance(&mut deps.querier, 1500);
+    deps.querier.update_balance(
+        env.contract.address.clone(),
+        vec![Coin::new(90000, "ujuno"), Coin::new(7, "uatom")],
+    );
+
+    let sweep = |denom: Denom| ExecuteMsg::Sweep {
+        denom,
+        recipient: "recipient".to_string(),
+    };
+
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("user1", &[]),
+        sweep(Denom::Native("uatom".to_string())),
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized {}));
+
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("owner", &[]),
+        sweep(Denom::Cw20(Addr::unchecked("lp_token_contract"))),
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages[0].msg,
+        CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "lp_token_contract".to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: "recipient".to_string(),
+                amount: Uint128::new(500),
+            })
+            .unwrap(),
+            funds: vec![],
+        })
+    );
+
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("owner", &[]),
+        sweep(Denom::Native("ujuno".to_string())),
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages[0].msg,
+        CosmosMsg::Bank(BankMsg::Send {
+            to_address: "recipient".to_string(),
+            amount: vec![Coin::new(3600, "ujuno")],
+        })
+    );
+
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("owner", &[]),
+        sweep(Denom::Native("uatom".to_string())),
+    )
+    .unwrap();
+    assert_eq!(res.attributes[2].value, "7");
+
+    // only owed rewards left
+    deps.querier.update_balance(
+        env.contract.address.clone(),
+        vec![Coin::new(86400, "ujuno")],
+    );
+    let err = execute(
+        deps.as_mut(),
+        env,
+        mock_info("owner", &[]),
+        sweep(Denom::Native("ujuno".to_string())),
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::NothingToSweep {}));
+}
+
+#[test]
+fn test_reward_fee() {
+    let mut deps = mock_dependencies();
+    let mut env = mock_env();
+    let mut instantiate_msg = InstantiateMsg {
+        distribution_schedule: vec![(
+            env.block.time.seconds(),
+            env.block.time.seconds() + 86400,
+            Uint128::new(86400),
+        )],
+        reward_fee_bps: Some(500),
+        ..default_instantiate_msg()
+    };
+    let err = instantiate(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("owner", &[]),
+        instantiate_msg.clone(),
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::TreasuryNotSet {}));
+
+    instantiate_msg.treasury = Some("treasury".to_string());
+    instantiate(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("owner", &[]),
+        instantiate_msg,
+    )
+    .unwrap();
+    bond_lp(deps.as_mut(), env.clone(), "user1", 1000);
+    env.block.time = env.block.time.plus_seconds(100);
+
+    let staker_info = query_staker_info(deps.as_ref(), env.clone(), "user1".to_string()).unwrap();
+    assert_eq!(staker_info.pending_reward, Uint128::new(100));
+    assert_eq!(staker_info.claimable_reward, Uint128::new(95));
+
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("user1", &[]),
+        ExecuteMsg::Withdraw {},
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages
+            .iter()
+            .map(|msg| msg.msg.clone())
+            .collect::<Vec<_>>(),
+        vec![
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "user1".to_string(),
+                amount: vec![Coin::new(95, "ujuno")],
+            }),
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "treasury".to_string(),
+                amount: vec![Coin::new(5, "ujuno")],
+            }),
+        ]
+    );
+    assert_eq!(res.attributes[3].value, "5");
+    let staker_info = query_staker_info(deps.as_ref(), env.clone(), "user1".to_string()).unwrap();
+    assert_eq!(staker_info.total_earned, Uint128::new(95));
+
+    let err = execute(
+        deps.as_mut(),
+        env,
+        mock_info("owner", &[]),
+        ExecuteMsg::UpdateRewardFee {
+            reward_fee_bps: 1001,
+        },
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::FeeTooHigh { max_bps: 1000 }));
+}
+
+#[test]
+fn test_deposit_fee_and_min_bond() {
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+    let instantiate_msg = InstantiateMsg {
+        distribution_schedule: vec![(
+            env.block.time.seconds(),
+            env.block.time.seconds() + 86400,
+            Uint128::new(86400),
+        )],
+        treasury: Some("treasury".to_string()),
+        deposit_fee_bps: Some(100),
+        min_bond_amount: Some(Uint128::new(500)),
+        ..default_instantiate_msg()
+    };
+    instantiate(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("owner", &[]),
+        instantiate_msg,
+    )
+    .unwrap();
+
+    let bond_msg = |amount: u128| {
+        ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "user1".to_string(),
+            amount: Uint128::new(amount),
+            msg: to_binary(&Cw20HookMsg::Bond { referrer: None }).unwrap(),
+        })
+    };
+    let info = mock_info("lp_token_contract", &[]);
+
+    let err = execute(deps.as_mut(), env.clone(), info.clone(), bond_msg(400)).unwrap_err();
+    assert!(matches!(
+        err,
+        ContractError::BelowMinimumBond { min_bond_amount } if min_bond_amount == Uint128::new(500)
+    ));
+
+    let res = execute(deps.as_mut(), env.clone(), info.clone(), bond_msg(1000)).unwrap();
+    assert_eq!(
+        res.messages[0].msg,
+        CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "lp_token_contract".to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: "treasury".to_string(),
+                amount: Uint128::new(10),
+            })
+            .unwrap(),
+            funds: vec![],
+        })
+    );
+    assert_eq!(res.attributes[2].value, "990");
+
+    // small top-ups of a large enough position are fine
+    execute(deps.as_mut(), env.clone(), info, bond_msg(10)).unwrap();
+    let staker_info = query_staker_info(deps.as_ref(), env, "user1".to_string()).unwrap();
+    assert_eq!(staker_info.bond_amount, Uint128::new(1000));
+}
+
+#[test]
+fn test_bond_caps() {
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+    setup_schedule(deps.as_mut(), env.clone(), 86400, 86400);
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("owner", &[]),
+        ExecuteMsg::UpdateBondCaps {
+            max_total_bond: Some(Uint128::new(1500)),
+            max_bond_per_address: Some(Uint128::new(1000)),
+        },
+    )
+    .unwrap();
+    bond_lp(deps.as_mut(), env.clone(), "user1", 800);
+
+    let bond_msg = |staker: &str, amount: u128| {
+        ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: staker.to_string(),
+            amount: Uint128::new(amount),
+            msg: to_binary(&Cw20HookMsg::Bond { referrer: None }).unwrap(),
+        })
+    };
+    let info = mock_info("lp_token_contract", &[]);
+
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        info.clone(),
+        bond_msg("user1", 300),
+    )
+    .unwrap_err();
+    assert!(matches!(
+        err,
+        ContractError::AddressBondCapExceeded { remaining } if remaining == Uint128::new(200)
+    ));
+
+    let err = execute(deps.as_mut(), env.clone(), info, bond_msg("user2", 800)).unwrap_err();
+    assert!(matches!(
+        err,
+        ContractError::TotalBondCapExceeded { remaining } if remaining == Uint128::new(700)
+    ));
+
+    let capacity = query_capacity(deps.as_ref()).unwrap();
+    assert_eq!(capacity.total_bond_amount, Uint128::new(800));
+    assert_eq!(capacity.remaining_total_bond, Some(Uint128::new(700)));
+
+    bond_lp(deps.as_mut(), env, "user2", 700);
+    let capacity = query_capacity(deps.as_ref()).unwrap();
+    assert_eq!(capacity.remaining_total_bond, Some(Uint128::zero()));
+}
+
+#[test]
+fn test_allowlist() {
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+    setup_schedule(deps.as_mut(), env.clone(), 86400, 86400);
+    let owner = mock_info("owner", &[]);
+    let update_mode = |allowlist_mode| ExecuteMsg::UpdateAllowlistMode { allowlist_mode };
+    let bond_msg = |staker: &str| {
+        ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: staker.to_string(),
+            amount: Uint128::new(1000),
+            msg: to_binary(&Cw20HookMsg::Bond { referrer: None }).unwrap(),
+        })
+    };
+    let lp_info = mock_info("lp_token_contract", &[]);
+
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        owner.clone(),
+        update_mode(AllowlistMode::Internal),
+    )
+    .unwrap();
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        lp_info.clone(),
+        bond_msg("user1"),
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::NotAllowlisted {}));
+    let res = query_simulate_bond(
+        deps.as_ref(),
+        env.clone(),
+        "user1".to_string(),
+        Uint128::new(1000),
+    )
+    .unwrap();
+    assert_eq!(
+        res.error,
+        Some(ContractError::NotAllowlisted {}.to_string())
+    );
+
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        owner.clone(),
+        ExecuteMsg::AddToAllowlist {
+            addresses: vec!["user1".to_string()],
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        query_allowlist(deps.as_ref(), None, None)
+            .unwrap()
+            .addresses,
+        vec!["user1".to_string()]
+    );
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        lp_info.clone(),
+        bond_msg("user1"),
+    )
+    .unwrap();
+
+    // removed stakers can still leave
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        owner.clone(),
+        ExecuteMsg::RemoveFromAllowlist {
+            addresses: vec!["user1".to_string()],
+        },
+    )
+    .unwrap();
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        lp_info.clone(),
+        bond_msg("user1"),
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::NotAllowlisted {}));
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("user1", &[]),
+        ExecuteMsg::Unbond {
+            amount: Uint128::new(1000),
+        },
+    )
+    .unwrap();
+
+    // cw4 group membership is checked at bond time
+    deps.querier.update_wasm(|query| match query {
+        WasmQuery::Smart { contract_addr, msg } if contract_addr == "group" => {
+            match from_binary(msg).unwrap() {
+                Cw4QueryMsg::Member { addr, .. } => SystemResult::Ok(ContractResult::Ok(
+                    to_binary(&MemberResponse {
+                        weight: if addr == "user2" { Some(1) } else { None },
+                    })
+                    .unwrap(),
+                )),
+                _ => panic!("unexpected cw4 query"),
+            }
+        }
+        _ => panic!("unexpected wasm query"),
+    });
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        owner,
+        update_mode(AllowlistMode::Cw4Group {
+            group: "group".to_string(),
+        }),
+    )
+    .unwrap();
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        lp_info.clone(),
+        bond_msg("user2"),
+    )
+    .unwrap();
+    let err = execute(deps.as_mut(), env, lp_info, bond_msg("user3")).unwrap_err();
+    assert!(matches!(err, ContractError::NotAllowlisted {}));
+}
+
+#[test]
+fn test_stake_changed_hooks() {
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+    setup_schedule(deps.as_mut(), env.clone(), 86400, 86400);
+
+    let add_hook = ExecuteMsg::AddHook {
+        addr: "hook1".to_string(),
+    };
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("user1", &[]),
+        add_hook.clone(),
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized {}));
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("owner", &[]),
+        add_hook.clone(),
+    )
+    .unwrap();
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("owner", &[]),
+        add_hook,
+    )
+    .unwrap_err();
+    assert!(matches!(
+        err,
+        ContractError::Hook(HookError::HookAlreadyRegistered {})
+    ));
+
+    let hooks: HooksResponse =
+        from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::Hooks {}).unwrap()).unwrap();
+    assert_eq!(hooks.hooks, vec!["hook1".to_string()]);
+
+    let info = mock_info("lp_token_contract", &[]);
+    let bond_msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "user1".to_string(),
+        amount: Uint128::new(1000),
+        msg: to_binary(&Cw20HookMsg::Bond { referrer: None }).unwrap(),
+    });
+    let res = execute(deps.as_mut(), env.clone(), info, bond_msg).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(
+            StakeChangedHookMsg::Stake {
+                addr: "user1".to_string(),
+                amount: Uint128::new(1000),
+            }
+            .into_cosmos_msg("hook1")
+            .unwrap()
+        )]
+    );
+
+    let unbond_msg = ExecuteMsg::Unbond {
+        amount: Uint128::new(400),
+    };
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("user1", &[]),
+        unbond_msg,
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(
+            StakeChangedHookMsg::Unstake {
+                addr: "user1".to_string(),
+                amount: Uint128::new(400),
+            }
+            .into_cosmos_msg("hook1")
+            .unwrap()
+        )]
+    );
+
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("owner", &[]),
+        ExecuteMsg::RemoveHook {
+            addr: "hook1".to_string(),
+        },
+    )
+    .unwrap();
+    let res = execute(
+        deps.as_mut(),
+        env,
+        mock_info("user1", &[]),
+        ExecuteMsg::Unbond {
+            amount: Uint128::new(100),
+        },
+    )
+    .unwrap();
+    assert!(res.messages.is_empty());
+}
+
+#[test]
+fn test_cw4_members() {
+    let mut deps = mock_dependencies();
+    let mut env = mock_env();
+    let instantiate_msg = InstantiateMsg {
+        distribution_schedule: vec![(
+            env.block.time.seconds(),
+            env.block.time.seconds() + 86400,
+            Uint128::new(86400),
+        )],
+        weight_divisor: Some(Uint128::new(10)),
+        ..default_instantiate_msg()
+    };
+    instantiate(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("owner", &[]),
+        instantiate_msg,
+    )
+    .unwrap();
+    let height = env.block.height;
+
+    bond_lp(deps.as_mut(), env.clone(), "user1", 1005);
+    env.block.height += 1;
+    bond_lp(deps.as_mut(), env.clone(), "user2", 500);
+    assert_eq!(query_total_weight(deps.as_ref()).unwrap().weight, 150);
+
+    let members = query_list_members(deps.as_ref(), None, None)
+        .unwrap()
+        .members;
+    assert_eq!(
+        members,
+        vec![
+            Member {
+                addr: "user1".to_string(),
+                weight: 100,
+            },
+            Member {
+                addr: "user2".to_string(),
+                weight: 50,
+            },
+        ]
+    );
+
+    env.block.height += 1;
+    execute(
+        deps.as_mut(),
+        env,
+        mock_info("user1", &[]),
+        ExecuteMsg::Unbond {
+            amount: Uint128::new(1005),
+        },
+    )
+    .unwrap();
+    assert_eq!(query_total_weight(deps.as_ref()).unwrap().weight, 50);
+
+    // snapshots keep the weight a member had at the start of each block
+    let weight_at = |height: Option<u64>| {
+        query_member(deps.as_ref(), "user1".to_string(), height)
+            .unwrap()
+            .weight
+    };
+    assert_eq!(weight_at(Some(height)), None);
+    assert_eq!(weight_at(Some(height + 1)), Some(100));
+    assert_eq!(weight_at(Some(height + 2)), Some(100));
+    assert_eq!(weight_at(Some(height + 3)), None);
+    assert_eq!(weight_at(None), None);
+}
+
+fn power_at(deps: Deps, env: Env, height: Option<u64>) -> (u128, u128) {
+    let user1 = query_voting_power_at_height(deps, env.clone(), "user1".to_string(), height)
+        .unwrap()
+        .power;
+    let total = query_total_power_at_height(deps, env, height)
+        .unwrap()
+        .power;
+    (user1.u128(), total.u128())
+}
+
+#[test]
+fn test_voting_power() {
+    let mut deps = mock_dependencies();
+    let mut env = mock_env();
+    setup_schedule(deps.as_mut(), env.clone(), 86400, 86400);
+    let height = env.block.height;
+
+    bond_lp(deps.as_mut(), env.clone(), "user1", 1000);
+    env.block.height += 1;
+    bond_lp(deps.as_mut(), env.clone(), "user2", 500);
+    env.block.height += 1;
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("user1", &[]),
+        ExecuteMsg::Unbond {
+            amount: Uint128::new(400),
+        },
+    )
+    .unwrap();
+
+    assert_eq!(power_at(deps.as_ref(), env.clone(), Some(height)), (0, 0));
+    assert_eq!(
+        power_at(deps.as_ref(), env.clone(), Some(height + 1)),
+        (1000, 1000)
+    );
+    assert_eq!(
+        power_at(deps.as_ref(), env.clone(), Some(height + 2)),
+        (1000, 1500)
+    );
+    assert_eq!(power_at(deps.as_ref(), env.clone(), None), (1000, 1500));
+    env.block.height += 1;
+    assert_eq!(power_at(deps.as_ref(), env.clone(), None), (600, 1100));
+
+    // no dao until the owner sets one
+    let res = query(deps.as_ref(), env.clone(), QueryMsg::Dao {});
+    assert!(res.is_err());
+
+    let msg = ExecuteMsg::UpdateVotingConfig {
+        dao: Some("dao".to_string()),
+        count_unbonding_power: true,
+    };
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("user1", &[]),
+        msg.clone(),
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized {}));
+    execute(deps.as_mut(), env.clone(), mock_info("owner", &[]), msg).unwrap();
+
+    let dao: Addr =
+        from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::Dao {}).unwrap()).unwrap();
+    assert_eq!(dao, Addr::unchecked("dao"));
+    let info: InfoResponse =
+        from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::Info {}).unwrap()).unwrap();
+    assert_eq!(info.info.version, "0.2.0");
+
+    // unbonding LP counts until it is redeemed
+    assert_eq!(power_at(deps.as_ref(), env.clone(), None), (1000, 1500));
+    env.block.time = env.block.time.plus_seconds(3600);
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("user1", &[]),
+        ExecuteMsg::Redeem {},
+    )
+    .unwrap();
+    env.block.height += 1;
+    assert_eq!(power_at(deps.as_ref(), env.clone(), None), (600, 1100));
+    assert_eq!(power_at(deps.as_ref(), env, Some(height + 3)), (1000, 1500));
+}
+
+#[test]
+fn test_receipt_token() {
+    let mut deps = mock_dependencies();
+    let mut env = mock_env();
+    let instantiate_msg = InstantiateMsg {
+        distribution_schedule: vec![(
+            env.block.time.seconds(),
+            env.block.time.seconds() + 86400,
+            Uint128::new(86400),
+        )],
+        receipt_token: Some(ReceiptTokenInfo {
+            code_id: 7,
+            name: "Hopers LP Receipt".to_string(),
+            symbol: "sLP".to_string(),
+            decimals: 6,
+        }),
+        ..default_instantiate_msg()
+    };
+    let res = instantiate(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("owner", &[]),
+        instantiate_msg,
+    )
+    .unwrap();
+    assert_eq!(res.messages.len(), 1);
+    assert_eq!(res.messages[0].id, 1);
+
+    // protobuf encoded MsgInstantiateContractResponse
+    let receipt_token = "receipt_token";
+    let mut data = vec![0x0a, receipt_token.len() as u8];
+    data.extend_from_slice(receipt_token.as_bytes());
+    reply(
+        deps.as_mut(),
+        env.clone(),
+        Reply {
+            id: 1,
+            result: SubMsgResult::Ok(SubMsgExecutionResponse {
+                events: vec![],
+                data: Some(data.into()),
+            }),
+        },
+    )
+    .unwrap();
+
+    let info = mock_info("lp_token_contract", &[]);
+    let cw20_rcv_msg = Cw20ReceiveMsg {
+        sender: "user1".to_string(),
+        amount: Uint128::new(1000),
+        msg: to_binary(&Cw20HookMsg::Bond { referrer: None }).unwrap(),
+    };
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info,
+        ExecuteMsg::Receive(cw20_rcv_msg),
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(WasmMsg::Execute {
+            contract_addr: receipt_token.to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Mint {
+                recipient: "user1".to_string(),
+                amount: Uint128::new(1000),
+            })
+            .unwrap(),
+            funds: vec![],
+        })]
+    );
+
+    // only the receipt token reports transfers
+    env.block.time = env.block.time.plus_seconds(100);
+    let msg = ExecuteMsg::TransferReceipt {
+        from: "user1".to_string(),
+        to: "user2".to_string(),
+        amount: Uint128::new(400),
+    };
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("user1", &[]),
+        msg.clone(),
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized {}));
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(receipt_token, &[]),
+        msg,
+    )
+    .unwrap();
+
+    // rewards accrued before the transfer stay with the sender
+    env.block.time = env.block.time.plus_seconds(100);
+    let staker1_info = query_staker_info(deps.as_ref(), env.clone(), "user1".to_string()).unwrap();
+    let staker2_info = query_staker_info(deps.as_ref(), env.clone(), "user2".to_string()).unwrap();
+    assert_eq!(staker1_info.bond_amount, Uint128::new(600));
+    assert_eq!(staker1_info.pending_reward, Uint128::new(160));
+    assert_eq!(staker2_info.bond_amount, Uint128::new(400));
+    assert_eq!(staker2_info.pending_reward, Uint128::new(40));
+    let state = query_state(deps.as_ref(), None).unwrap();
+    assert_eq!(state.total_bond_amount, Uint128::new(1000));
+
+    // a transfer to oneself leaves the position as it is
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(receipt_token, &[]),
+        ExecuteMsg::TransferReceipt {
+            from: "user1".to_string(),
+            to: "user1".to_string(),
+            amount: Uint128::new(600),
+        },
+    )
+    .unwrap();
+    let staker1_info = query_staker_info(deps.as_ref(), env.clone(), "user1".to_string()).unwrap();
+    assert_eq!(staker1_info.shares, Uint128::new(600));
+    let state = query_state(deps.as_ref(), None).unwrap();
+    assert_eq!(state.total_shares, Uint128::new(1000));
+
+    // the receiver must be allowed to bond what it receives
+    for msg in [
+        ExecuteMsg::UpdateAllowlistMode {
+            allowlist_mode: AllowlistMode::Internal,
+        },
+        ExecuteMsg::UpdateBondCaps {
+            max_total_bond: None,
+            max_bond_per_address: Some(Uint128::new(50)),
+        },
+    ] {
+        execute(deps.as_mut(), env.clone(), mock_info("owner", &[]), msg).unwrap();
+    }
+    let transfer = ExecuteMsg::TransferReceipt {
+        from: "user1".to_string(),
+        to: "user3".to_string(),
+        amount: Uint128::new(100),
+    };
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(receipt_token, &[]),
+        transfer.clone(),
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::NotAllowlisted {}));
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("owner", &[]),
+        ExecuteMsg::AddToAllowlist {
+            addresses: vec!["user3".to_string()],
+        },
+    )
+    .unwrap();
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(receipt_token, &[]),
+        transfer,
+    )
+    .unwrap_err();
+    assert!(matches!(
+        err,
+        ContractError::AddressBondCapExceeded { remaining } if remaining == Uint128::new(50)
+    ));
+
+    // receipts are sent back to unbond, which the receipt token also reports
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("user2", &[]),
+        ExecuteMsg::Unbond {
+            amount: Uint128::new(400),
+        },
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::ReceiptsRequired {}));
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(receipt_token, &[]),
+        ExecuteMsg::TransferReceipt {
+            from: "user2".to_string(),
+            to: env.contract.address.to_string(),
+            amount: Uint128::new(400),
+        },
+    )
+    .unwrap();
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(receipt_token, &[]),
+        ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "user2".to_string(),
+            amount: Uint128::new(400),
+            msg: to_binary(&Cw20HookMsg::Unbond {}).unwrap(),
+        }),
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(WasmMsg::Execute {
+            contract_addr: receipt_token.to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Burn {
+                amount: Uint128::new(400),
+            })
+            .unwrap(),
+            funds: vec![],
+        })]
+    );
+    let staker2_info = query_staker_info(deps.as_ref(), env, "user2".to_string()).unwrap();
+    assert_eq!(staker2_info.shares, Uint128::zero());
+}
+
+#[test]
+fn test_share_mode() {
+    let mut deps = mock_dependencies();
+    let mut env = mock_env();
+    setup_schedule(deps.as_mut(), env.clone(), 86400, 86400);
+
+    // compounding needs share mode
+    let compound = |sender: &str, amount: u128| {
+        ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: sender.to_string(),
+            amount: Uint128::new(amount),
+            msg: to_binary(&Cw20HookMsg::Compound {}).unwrap(),
+        })
+    };
+    bond_lp(deps.as_mut(), env.clone(), "user1", 1000);
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("lp_token_contract", &[]),
+        compound("owner", 500),
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::ShareModeDisabled {}));
+
+    let mut deps = mock_dependencies();
+    let instantiate_msg = InstantiateMsg {
+        distribution_schedule: vec![(
+            env.block.time.seconds(),
+            env.block.time.seconds() + 86400,
+            Uint128::new(86400),
+        )],
+        share_mode: Some(true),
+        ..default_instantiate_msg()
+    };
+    instantiate(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("owner", &[]),
+        instantiate_msg,
+    )
+    .unwrap();
+    bond_lp(deps.as_mut(), env.clone(), "user1", 1000);
+
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("lp_token_contract", &[]),
+        compound("user1", 500),
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized {}));
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("lp_token_contract", &[]),
+        compound("owner", 500),
+    )
+    .unwrap();
+
+    let share_value: ShareValueResponse =
+        from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::ShareValue {}).unwrap()).unwrap();
+    assert_eq!(share_value.total_shares, Uint128::new(1000));
+    assert_eq!(share_value.total_bond_amount, Uint128::new(1500));
+    assert_eq!(share_value.share_value, Decimal256::percent(150));
+    // voting power counts shares, which compounding does not change
+    let next_height = Some(env.block.height + 1);
+    assert_eq!(
+        power_at(deps.as_ref(), env.clone(), next_height),
+        (1000, 1000)
+    );
+
+    // new bonds buy shares at the compounded value and rewards follow shares
+    bond_lp(deps.as_mut(), env.clone(), "user2", 300);
+    env.block.time = env.block.time.plus_seconds(120);
+    let staker1_info = query_staker_info(deps.as_ref(), env.clone(), "user1".to_string()).unwrap();
+    let staker2_info = query_staker_info(deps.as_ref(), env.clone(), "user2".to_string()).unwrap();
+    assert_eq!(staker1_info.shares, Uint128::new(1000));
+    assert_eq!(staker1_info.bond_amount, Uint128::new(1000));
+    assert_eq!(staker1_info.bond_value, Uint128::new(1500));
+    assert_eq!(staker1_info.pending_reward, Uint128::new(100));
+    assert_eq!(staker2_info.shares, Uint128::new(200));
+    assert_eq!(staker2_info.bond_value, Uint128::new(300));
+    assert_eq!(staker2_info.pending_reward, Uint128::new(20));
+    assert_eq!(
+        power_at(deps.as_ref(), env.clone(), next_height),
+        (1000, 1200)
+    );
+
+    let unbond = |amount: u128| ExecuteMsg::Unbond {
+        amount: Uint128::new(amount),
+    };
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("user1", &[]),
+        unbond(1501),
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::ExceedBondAmount {}));
+    let msg = ExecuteMsg::UpdateVotingConfig {
+        dao: None,
+        count_unbonding_power: true,
+    };
+    execute(deps.as_mut(), env.clone(), mock_info("owner", &[]), msg).unwrap();
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("user1", &[]),
+        unbond(1500),
+    )
+    .unwrap();
+    // unbonding power stays in the shares burned, not the compounded LP
+    assert_eq!(
+        power_at(deps.as_ref(), env.clone(), next_height),
+        (1000, 1200)
+    );
+
+    let state = query_state(deps.as_ref(), None).unwrap();
+    assert_eq!(state.total_bond_amount, Uint128::new(300));
+    assert_eq!(state.total_shares, Uint128::new(200));
+    let staker1_info = query_staker_info(deps.as_ref(), env.clone(), "user1".to_string()).unwrap();
+    assert_eq!(staker1_info.shares, Uint128::zero());
+    assert_eq!(staker1_info.bond_amount, Uint128::zero());
+
+    env.block.time = env.block.time.plus_seconds(3600);
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("user1", &[]),
+        ExecuteMsg::Redeem {},
+    )
+    .unwrap();
+    env.block.height += 1;
+    assert_eq!(power_at(deps.as_ref(), env.clone(), None), (0, 200));
+}
+
+#[test]
+fn test_reward_vesting() {
+    let mut deps = mock_dependencies();
+    let mut env = mock_env();
+    setup_schedule(deps.as_mut(), env.clone(), 86400, 86400);
+    let msg = ExecuteMsg::UpdateRewardVestingDuration {
+        reward_vesting_duration: 1000,
+    };
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("user1", &[]),
+        msg.clone(),
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized {}));
+    execute(deps.as_mut(), env.clone(), mock_info("owner", &[]), msg).unwrap();
+
+    bond_lp(deps.as_mut(), env.clone(), "user1", 1000);
+    env.block.time = env.block.time.plus_seconds(100);
+
+    // withdrawn rewards are locked instead of paid out
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("user1", &[]),
+        ExecuteMsg::Withdraw {},
+    )
+    .unwrap();
+    assert!(res.messages.is_empty());
+    let vesting = query_vesting(deps.as_ref(), env.clone(), "user1".to_string()).unwrap();
+    assert_eq!(vesting.locked_amount, Uint128::new(100));
+    assert_eq!(vesting.claimable_amount, Uint128::zero());
+    assert_eq!(vesting.end_time, env.block.time.seconds() + 1000);
+    let state = query_state(deps.as_ref(), None).unwrap();
+    assert_eq!(state.total_vesting_amount, Uint128::new(100));
+
+    env.block.time = env.block.time.plus_seconds(500);
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("user1", &[]),
+        ExecuteMsg::ClaimVested {},
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+            to_address: "user1".to_string(),
+            amount: vec![Coin {
+                denom: "ujuno".to_string(),
+                amount: Uint128::new(50),
+            }],
+        }))]
+    );
+
+    // a new withdrawal restarts vesting of what is still locked
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("user1", &[]),
+        ExecuteMsg::Withdraw {},
+    )
+    .unwrap();
+    let vesting = query_vesting(deps.as_ref(), env.clone(), "user1".to_string()).unwrap();
+    assert_eq!(vesting.locked_amount, Uint128::new(550));
+    assert_eq!(vesting.end_time, env.block.time.seconds() + 1000);
+
+    env.block.time = env.block.time.plus_seconds(2000);
+    let vesting = query_vesting(deps.as_ref(), env.clone(), "user1".to_string()).unwrap();
+    assert_eq!(vesting.locked_amount, Uint128::zero());
+    assert_eq!(vesting.claimable_amount, Uint128::new(550));
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("user1", &[]),
+        ExecuteMsg::ClaimVested {},
+    )
+    .unwrap();
+    let err = execute(
+        deps.as_mut(),
+        env,
+        mock_info("user1", &[]),
+        ExecuteMsg::ClaimVested {},
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::NothingToClaim {}));
+    let state = query_state(deps.as_ref(), None).unwrap();
+    assert_eq!(state.total_vesting_amount, Uint128::zero());
+}
+
+#[test]
+fn test_loyalty_multiplier() {
+    let mut deps = mock_dependencies();
+    let mut env = mock_env();
+    let start = env.block.time.seconds();
+    setup_schedule(deps.as_mut(), env.clone(), 86400, 86400);
+
+    let update =
+        |loyalty_multipliers: Vec<(u64, Decimal256)>| ExecuteMsg::UpdateLoyaltyMultipliers {
+            loyalty_multipliers,
+        };
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("owner", &[]),
+        update(vec![(1000, Decimal256::percent(50))]),
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::InvalidLoyaltyMultipliers {}));
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("owner", &[]),
+        update(vec![(1000, Decimal256::percent(200))]),
+    )
+    .unwrap();
+
+    bond_lp(deps.as_mut(), env.clone(), "user1", 1000);
+    bond_lp(deps.as_mut(), env.clone(), "user2", 1000);
+
+    // the multiplier applies once the staker is refreshed, by anyone
+    env.block.time = env.block.time.plus_seconds(1000);
+    let staker1_info = query_staker_info(deps.as_ref(), env.clone(), "user1".to_string()).unwrap();
+    assert_eq!(staker1_info.loyalty_multiplier, Decimal256::percent(200));
+    assert_eq!(staker1_info.reward_weight, Uint128::new(1000));
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("user3", &[]),
+        ExecuteMsg::RefreshLoyalty {
+            staker: "user1".to_string(),
+        },
+    )
+    .unwrap();
+
+    env.block.time = env.block.time.plus_seconds(300);
+    let staker1_info = query_staker_info(deps.as_ref(), env.clone(), "user1".to_string()).unwrap();
+    let staker2_info = query_staker_info(deps.as_ref(), env.clone(), "user2".to_string()).unwrap();
+    assert_eq!(staker1_info.reward_weight, Uint128::new(2000));
+    assert_eq!(staker1_info.pending_reward, Uint128::new(700));
+    assert_eq!(staker2_info.pending_reward, Uint128::new(600));
+
+    // unbonding half of the stake halves its age
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("user1", &[]),
+        ExecuteMsg::Unbond {
+            amount: Uint128::new(500),
+        },
+    )
+    .unwrap();
+    let staker1_info = query_staker_info(deps.as_ref(), env.clone(), "user1".to_string()).unwrap();
+    assert_eq!(staker1_info.staked_since, start + 650);
+    assert_eq!(staker1_info.loyalty_multiplier, Decimal256::one());
+    assert_eq!(staker1_info.reward_weight, Uint128::new(500));
+    let state = query_state(deps.as_ref(), None).unwrap();
+    assert_eq!(state.total_reward_weight, Uint128::new(1500));
+}
+
+fn lock_nft(deps: DepsMut, env: Env, collection: &str, sender: &str) -> Result<(), ContractError> {
+    execute(
+        deps,
+        env,
+        mock_info(collection, &[]),
+        ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+            sender: sender.to_string(),
+            token_id: "1".to_string(),
+            msg: to_binary(&"").unwrap(),
+        }),
+    )
+    .map(|_| ())
+}
+
+#[test]
+fn test_nft_boost() {
+    let mut deps = mock_dependencies();
+    let mut env = mock_env();
+    setup_schedule(deps.as_mut(), env.clone(), 86400, 86400);
+
+    let update = |nft_boosts: Vec<(String, Decimal256)>| ExecuteMsg::UpdateNftBoosts { nft_boosts };
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("owner", &[]),
+        update(vec![("nft".to_string(), Decimal256::zero())]),
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::InvalidNftBoosts {}));
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("owner", &[]),
+        update(vec![("nft".to_string(), Decimal256::one())]),
+    )
+    .unwrap();
+
+    bond_lp(deps.as_mut(), env.clone(), "user1", 1000);
+    bond_lp(deps.as_mut(), env.clone(), "user2", 1000);
+
+    let err = lock_nft(deps.as_mut(), env.clone(), "other_nft", "user1").unwrap_err();
+    assert!(matches!(err, ContractError::NftCollectionNotAllowed {}));
+    lock_nft(deps.as_mut(), env.clone(), "nft", "user1").unwrap();
+    let err = lock_nft(deps.as_mut(), env.clone(), "nft", "user1").unwrap_err();
+    assert!(matches!(err, ContractError::NftAlreadyLocked {}));
+
+    let res: LockedNftsResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::LockedNfts {
+                staker: "user1".to_string(),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        res.nfts,
+        vec![LockedNftEntry {
+            collection: "nft".to_string(),
+            token_id: "1".to_string(),
+            boost: Decimal256::one(),
+        }]
+    );
+
+    // the boosted staker earns twice as much
+    env.block.time = env.block.time.plus_seconds(300);
+    let staker1_info = query_staker_info(deps.as_ref(), env.clone(), "user1".to_string()).unwrap();
+    let staker2_info = query_staker_info(deps.as_ref(), env.clone(), "user2".to_string()).unwrap();
+    assert_eq!(staker1_info.reward_weight, Uint128::new(2000));
+    assert_eq!(staker1_info.pending_reward, Uint128::new(200));
+    assert_eq!(staker2_info.pending_reward, Uint128::new(100));
+
+    // unlocking settles the boosted rewards and returns the NFT
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("user1", &[]),
+        ExecuteMsg::UnlockNft {
+            collection: "nft".to_string(),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages[0].msg,
+        CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "nft".to_string(),
+            msg: to_binary(&Cw721ExecuteMsg::TransferNft {
+                recipient: "user1".to_string(),
+                token_id: "1".to_string(),
+            })
+            .unwrap(),
+            funds: vec![]
+        })
+    );
+    let staker1_info = query_staker_info(deps.as_ref(), env.clone(), "user1".to_string()).unwrap();
+    assert_eq!(staker1_info.nft_boost, Decimal256::zero());
+    assert_eq!(staker1_info.reward_weight, Uint128::new(1000));
+    assert_eq!(staker1_info.pending_reward, Uint128::new(200));
+    let state = query_state(deps.as_ref(), None).unwrap();
+    assert_eq!(state.total_reward_weight, Uint128::new(2000));
+
+    let err = execute(
+        deps.as_mut(),
+        env,
+        mock_info("user1", &[]),
+        ExecuteMsg::UnlockNft {
+            collection: "nft".to_string(),
+        },
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::NftNotLocked {}));
+}
+
+fn bond_referred(
+    deps: DepsMut,
+    env: Env,
+    staker: &str,
+    referrer: &str,
+) -> Result<(), ContractError> {
+    let cw20_rcv_msg = Cw20ReceiveMsg {
+        sender: staker.to_string(),
+        amount: Uint128::new(1000),
+        msg: to_binary(&Cw20HookMsg::Bond {
+            referrer: Some(referrer.to_string()),
+        })
+        .unwrap(),
+    };
+    execute(
+        deps,
+        env,
+        mock_info("lp_token_contract", &[]),
+        ExecuteMsg::Receive(cw20_rcv_msg),
+    )
+    .map(|_| ())
+}
+
+fn referral_earnings(deps: Deps) -> ReferralEarningsResponse {
+    from_binary(
+        &query(
+            deps,
+            mock_env(),
+            QueryMsg::ReferralEarnings {
+                referrer: "referrer".to_string(),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap()
+}
+
+fn referees(deps: Deps, env: Env) -> Vec<String> {
+    let res: ReferralsResponse = from_binary(
+        &query(
+            deps,
+            env,
+            QueryMsg::Referrals {
+                referrer: "referrer".to_string(),
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    res.referees
+}
+
+#[test]
+fn test_referrals() {
+    let mut deps = mock_dependencies();
+    let mut env = mock_env();
+    setup_schedule(deps.as_mut(), env.clone(), 86400, 86400);
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("owner", &[]),
+        ExecuteMsg::UpdateReferralFee {
+            referral_fee_bps: 1000,
+        },
+    )
+    .unwrap();
+
+    let err = bond_referred(deps.as_mut(), env.clone(), "user1", "user1").unwrap_err();
+    assert!(matches!(err, ContractError::InvalidReferrer {}));
+    bond_referred(deps.as_mut(), env.clone(), "user1", "referrer").unwrap();
+    bond_lp(deps.as_mut(), env.clone(), "user2", 1000);
+    assert_eq!(
+        referees(deps.as_ref(), env.clone()),
+        vec!["user1".to_string()]
+    );
+
+    // 10% of the referee's rewards go to the referrer
+    env.block.time = env.block.time.plus_seconds(1000);
+    let staker1_info = query_staker_info(deps.as_ref(), env.clone(), "user1".to_string()).unwrap();
+    let staker2_info = query_staker_info(deps.as_ref(), env.clone(), "user2".to_string()).unwrap();
+    assert_eq!(staker1_info.referrer, Some("referrer".to_string()));
+    assert_eq!(staker1_info.pending_reward, Uint128::new(450));
+    assert_eq!(staker2_info.pending_reward, Uint128::new(500));
+    // the cut is credited when the referee's rewards are settled
+    let earnings = referral_earnings(deps.as_ref());
+    assert_eq!(earnings.pending, Uint128::zero());
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("user1", &[]),
+        ExecuteMsg::Withdraw {},
+    )
+    .unwrap();
+    let state = query_state(deps.as_ref(), None).unwrap();
+    assert_eq!(state.total_referral_reward, Uint128::new(50));
+    let earnings = referral_earnings(deps.as_ref());
+    assert_eq!(earnings.pending, Uint128::new(50));
+
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("referrer", &[]),
+        ExecuteMsg::ClaimReferralRewards {},
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages[0].msg,
+        CosmosMsg::Bank(BankMsg::Send {
+            to_address: "referrer".to_string(),
+            amount: vec![Coin {
+                denom: "ujuno".to_string(),
+                amount: Uint128::new(50),
+            }],
+        })
+    );
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("referrer", &[]),
+        ExecuteMsg::ClaimReferralRewards {},
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::NothingToClaim {}));
+    let earnings = referral_earnings(deps.as_ref());
+    assert_eq!(earnings.pending, Uint128::zero());
+    assert_eq!(earnings.total, Uint128::new(50));
+
+    // closing the position ends the referral
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("user1", &[]),
+        ExecuteMsg::Unbond {
+            amount: Uint128::new(1000),
+        },
+    )
+    .unwrap();
+    assert!(referees(deps.as_ref(), env.clone()).is_empty());
+
+    // a position holding only a locked NFT is opened by its first bond
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("owner", &[]),
+        ExecuteMsg::UpdateNftBoosts {
+            nft_boosts: vec![("nft".to_string(), Decimal256::one())],
+        },
+    )
+    .unwrap();
+    lock_nft(deps.as_mut(), env.clone(), "nft", "user3").unwrap();
+    bond_referred(deps.as_mut(), env.clone(), "user3", "referrer").unwrap();
+    assert_eq!(
+        referees(deps.as_ref(), env.clone()),
+        vec!["user3".to_string()]
+    );
+    let staker3_info = query_staker_info(deps.as_ref(), env, "user3".to_string()).unwrap();
+    assert_eq!(staker3_info.referrer, Some("referrer".to_string()));
+}